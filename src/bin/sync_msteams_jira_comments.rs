@@ -1,53 +1,94 @@
 use sync_msteams_jira_comments::{
-    cfg::Config, jira_api::model::JiraAPI, ms_graph_api::model::MSGraphAPI, server::{AppState, Server}, utils::os_signal_or_completion_of
+    cfg::Config, http_client, i18n::model::Catalog, jira_api::model::JiraAPI, logging, metrics::model::Metrics, notifier::model::Notifier, ms_graph_api::model::MSGraphAPI, outbox::model::Outbox, reconciler::model::Reconciler, routes::model::Routes, scheduler::model::Scheduler, server::{AppState, Server}, store::model::Store, utils::os_signal_or_completion_of
 };
 
 use anyhow::{ Context, Result };
-use envconfig::Envconfig;
-use std::{sync::Arc, time::Duration};
+use std::{future::pending, sync::Arc, time::Duration};
+use tokio::task::JoinSet;
+use tracing::error;
 
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Tracing.
-    tracing_subscriber::fmt().with_max_level(tracing::Level::INFO).init();
     // Read configuration.
-    let cfg = Config::init_from_env().context("parse config")?;
+    let cfg = Config::load().context("parse config")?;
+    // Tracing, the guard flushes buffered lines on exit.
+    let _log_guard = logging::model::init(&cfg.logging)?;
+    // Retry policy of Jira and Graph requests
+    http_client::model::init(cfg.http_client.clone())?;
+    // Build Teams channel <-> Jira project routes
+    let routes = Routes::new(cfg.routes.clone(), &cfg.ms_graph_api, &cfg.jira);
     // Create MSGraphAPI instance
-    let graph_api = MSGraphAPI::new(cfg.ms_graph_api.clone())?;
+    let graph_api = MSGraphAPI::new(cfg.ms_graph_api.clone(), &routes)?;
     // Create JiraAPI instance
     let jira_api = JiraAPI::new(cfg.jira.clone())?;
+    // Open Teams <-> Jira links store
+    let store = Store::new(cfg.store.clone())?;
+    // Load translations of bot messages
+    let i18n = Catalog::new(cfg.i18n.clone()).context("load message catalogs")?;
     let state = AppState {
         jira: jira_api,
         microsoft: graph_api,
+        routes,
+        store,
+        outbox: Outbox::new(cfg.outbox.clone()),
+        scheduler: Scheduler::new(cfg.scheduler.clone()),
+        reconciler: Reconciler::new(cfg.reconciler.clone()),
+        i18n,
+        metrics: Metrics::new()?,
+        notifier: Notifier::new(cfg.notifier.clone()),
     };
     let state_shared = Arc::new(state);
     // Create API server.
     let api_server = Server::new();
     // Start API server, but do not call await.
     let api_server_future = api_server.start(cfg.clone(), state_shared.clone());
-    // Wait server start and init subscription
+    // Wait server start and init subscriptions
     let api = state_shared.clone();
     tokio::task::spawn(async move {
         tokio::time::sleep(std::time::Duration::from_secs(1)).await;
 
-        let mut tx = api.microsoft.state.lock().await;
-        let token = match tx.token.get() {
-            Ok(t) => t,
-            Err(_) => tx.token.renew(&api.microsoft.client, &api.microsoft.config).await.unwrap(),
-        };
-
-        tx.subscription.init(&api.microsoft.client, &api.microsoft.config, &token, false).await.unwrap();
+        api.microsoft.init_subscriptions().await;
     });
-    // Renew delegated access token when needed
+    // Background loops, the service stops if any of them fails
+    let mut tasks = JoinSet::new();
+    // Process accepted webhooks, including ones left from previous run
     let api = state_shared.clone();
-    tokio::task::spawn(async move {
-        api.microsoft.manage_granted_token().await.unwrap();
-    });
-    // Block until termination signal is received from OS or API server fails.
-    let api_server_result = os_signal_or_completion_of(api_server_future).await;
+    tasks.spawn(async move { Outbox::run(api).await.context("outbox") });
+    // Close issues left without confirmation, including ones scheduled before restart
+    let api = state_shared.clone();
+    tasks.spawn(async move { Scheduler::run(api).await.context("scheduler") });
+    // Sync messages and comments missed by webhooks
+    let api = state_shared.clone();
+    tasks.spawn(async move { Reconciler::run(api).await.context("reconciler") });
+    // Renew subscriptions before they expire
+    let api = state_shared.clone();
+    tasks.spawn(async move { api.microsoft.manage_subscriptions(&api.i18n, &api.notifier).await.context("subscriptions") });
+    // Restore delegated access token and renew it when needed
+    let api = state_shared.clone();
+    tasks.spawn(async move { api.microsoft.manage_granted_token(&api.store, &api.i18n, &api.notifier).await.context("delegated token") });
+    // Block until termination signal is received from OS, API server fails or a background loop fails.
+    let result = os_signal_or_completion_of(async {
+        tokio::select! {
+            result = api_server_future => result.context("API server"),
+            result = first_failure(tasks) => result,
+        }
+    }).await;
+
+    if let Err(e) = &result {
+        error!("Stopping: {:#}", e);
+    }
     // Gracefully stop API server if not already stopped.
     api_server.stop(Duration::from_secs(cfg.server.shutdown_timeout));
     // Return result.
-    api_server_result.context("API server")
+    result
+}
+
+/// Completes with the error of the first failed task. Tasks which finish successfully (e.g. disabled ones) are skipped.
+async fn first_failure(mut tasks: JoinSet<Result<()>>) -> Result<()> {
+    while let Some(result) = tasks.join_next().await {
+        result.context("background task panicked")??;
+    }
+
+    pending().await
 }
\ No newline at end of file
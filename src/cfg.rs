@@ -1,7 +1,23 @@
 use crate::server::cfg::Config as ServerConfig;
 use crate::ms_graph_api::cfg::Config as MsGraphApiConfig;
 use crate::jira_api::cfg::Config as JiraConfig;
+use crate::store::cfg::Config as StoreConfig;
+use crate::outbox::cfg::Config as OutboxConfig;
+use crate::reconciler::cfg::Config as ReconcilerConfig;
+use crate::routes::cfg::Config as RoutesConfig;
+use crate::scheduler::cfg::Config as SchedulerConfig;
+use crate::http_client::cfg::Config as HttpClientConfig;
+use crate::i18n::cfg::Config as I18nConfig;
+use crate::logging::cfg::Config as LoggingConfig;
+use crate::notifier::cfg::Config as NotifierConfig;
+use anyhow::{bail, Context, Result};
 use envconfig::Envconfig;
+use reqwest::Url;
+use serde_json::Value;
+use std::{collections::HashMap, env, net::SocketAddr, path::Path};
+
+/// Env var with path to the optional configuration file.
+const CONFIG_FILE_VAR: &str = "CONFIG_FILE";
 
 /// Generic configuration for any module.
 /// Configuration of particular modules is stored in DBMS and managed by
@@ -14,4 +30,210 @@ pub struct Config {
     pub ms_graph_api: MsGraphApiConfig,
     #[envconfig(nested)]
     pub jira: JiraConfig,
+    #[envconfig(nested)]
+    pub store: StoreConfig,
+    #[envconfig(nested)]
+    pub outbox: OutboxConfig,
+    #[envconfig(nested)]
+    pub routes: RoutesConfig,
+    #[envconfig(nested)]
+    pub scheduler: SchedulerConfig,
+    #[envconfig(nested)]
+    pub reconciler: ReconcilerConfig,
+    #[envconfig(nested)]
+    pub i18n: I18nConfig,
+    #[envconfig(nested)]
+    pub logging: LoggingConfig,
+    #[envconfig(nested)]
+    pub notifier: NotifierConfig,
+    #[envconfig(nested)]
+    pub http_client: HttpClientConfig,
+}
+
+impl Config {
+    /// Reads configuration from the file set in `CONFIG_FILE` (if any), overridden by env vars,
+    /// and validates it. Every missing or malformed setting is reported at once.
+    pub fn load() -> Result<Self> {
+        let mut vars = match env::var(CONFIG_FILE_VAR) {
+            Ok(path) if !path.is_empty() => read_file(Path::new(&path))
+                .with_context(|| format!("Failed to read config file {path}"))?,
+            _ => HashMap::new(),
+        };
+
+        // Empty env vars don't override the file, so unset ones in deployment templates keep file values
+        vars.extend(env::vars().filter(|(_, value)| !value.is_empty()));
+
+        let mut errors = Vec::new();
+
+        // Envconfig stops on the first malformed value, so drop it and try again to find the rest.
+        let config = loop {
+            match Self::init_from_hashmap(&vars) {
+                Ok(config) => break Some(config),
+                Err(envconfig::Error::ParseError { name }) => {
+                    errors.push(format!("{name}: malformed value"));
+
+                    // Malformed default
+                    if vars.remove(name).is_none() {
+                        break None;
+                    }
+                },
+                Err(envconfig::Error::EnvVarMissing { name }) => {
+                    // Dropped malformed value is already reported
+                    if !errors.iter().any(|e| e.starts_with(&format!("{name}:"))) {
+                        errors.push(format!("{name}: is required"));
+                    }
+
+                    break None;
+                },
+            }
+        };
+
+        if let Some(ref config) = config {
+            config.validate(&mut errors);
+        }
+
+        match config {
+            Some(config) if errors.is_empty() => Ok(config),
+            _ => bail!("Invalid configuration:\n - {}", errors.join("\n - ")),
+        }
+    }
+
+    fn validate(&self, errors: &mut Vec<String>) {
+        if self.server.addr.parse::<SocketAddr>().is_err() {
+            errors.push(format!("API_ADDR: \"{}\" is not a socket address", self.server.addr));
+        }
+
+        let microsoft = &self.ms_graph_api;
+        required(errors, "MICROSOFT_TENANT_ID", &microsoft.tenant_id);
+        required(errors, "MICROSOFT_CLIENT_ID", &microsoft.client_id);
+        required(errors, "MICROSOFT_CLIENT_SECRET", &microsoft.client_secret);
+        url(errors, "MICROSOFT_SUBSCRIPTION_NOTIFICATION_URL", &microsoft.notification_url);
+        url(errors, "MICROSOFT_SUBSCRIPTION_LIFECYCLE_NOTIFICATION_URL", &microsoft.lifecycle_notification_url);
+        url(errors, "MICROSOFT_OAUTH_URL", &microsoft.oauth_url);
+        email(errors, "TEAMS_USER", &microsoft.teams_user);
+
+        let jira = &self.jira;
+        email(errors, "JIRA_USER", &jira.user);
+        required(errors, "JIRA_SECRET", &jira.secret);
+        required(errors, "JIRA_TOKEN", &jira.token);
+        url(errors, "JIRA_BASE_URL", &jira.base_url);
+
+        let routes = &self.routes.routes.0;
+
+        if jira.reopen_enabled
+            && jira.reopen_status.trim().is_empty()
+            && (routes.is_empty() || routes.iter().any(|r| r.reopen_status.as_deref().is_none_or(|s| s.trim().is_empty())))
+        {
+            errors.push("JIRA_REOPEN_STATUS: is required when JIRA_REOPEN_ENABLED is set".to_string());
+        }
+
+        if routes.is_empty() {
+            required(errors, "TEAMS_GROUP_ID", &microsoft.group_id);
+            required(errors, "TEAMS_CHANNEL_ID", &microsoft.channel_id);
+            required(errors, "JIRA_PROJECT_KEY", &jira.project_key);
+            custom_field(errors, "JIRA_MSTEAMS_LINK_FIELD_NAME", &jira.msteams_link_field_name);
+            required(errors, "JIRA_MSTEAMS_LINK_FIELD_JQL_NAME", &jira.msteams_link_field_jql_name);
+        }
+
+        let notifier = &self.notifier;
+
+        if notifier.enabled
+            && notifier.emails.as_slice().is_empty()
+            && (notifier.group_id.trim().is_empty() || notifier.channel_id.trim().is_empty())
+        {
+            errors.push("ALERT_EMAILS: is required when ALERT_ENABLED is set and ALERT_GROUP_ID/ALERT_CHANNEL_ID aren't".to_string());
+        }
+
+        for (i, address) in notifier.emails.as_slice().iter().enumerate() {
+            email(errors, &format!("ALERT_EMAILS[{i}]"), address);
+        }
+
+        for (i, route) in routes.iter().enumerate() {
+            required(errors, &format!("ROUTES[{i}].group_id"), &route.group_id);
+            required(errors, &format!("ROUTES[{i}].channel_id"), &route.channel_id);
+            required(errors, &format!("ROUTES[{i}].project_key"), &route.project_key);
+            required(errors, &format!("ROUTES[{i}].issue_type"), &route.issue_type);
+            custom_field(errors, &format!("ROUTES[{i}].link_field_name"), &route.link_field_name);
+            required(errors, &format!("ROUTES[{i}].link_field_jql_name"), &route.link_field_jql_name);
+
+            if routes[..i].iter().any(|r| r.group_id == route.group_id && r.channel_id == route.channel_id) {
+                errors.push(format!("ROUTES[{i}]: channel is already routed"));
+            }
+        }
+    }
+}
+
+/// Reads TOML (or YAML for `.yaml`/`.yml` files) config file into env-like variables.
+/// Top-level tables are prefixes: `base_url` in `[jira]` table is `JIRA_BASE_URL`.
+/// Arrays and deeper tables are passed as JSON, so `[[routes]]` becomes `ROUTES`.
+fn read_file(path: &Path) -> Result<HashMap<String, String>> {
+    let content = std::fs::read_to_string(path)?;
+
+    let value: Value = match path.extension().and_then(|e| e.to_str()) {
+        Some("yaml" | "yml") => serde_yaml::from_str(&content)?,
+        _ => toml::from_str(&content)?,
+    };
+
+    let Value::Object(root) = value else {
+        bail!("Config file must contain a table");
+    };
+
+    let mut vars = HashMap::new();
+
+    for (key, value) in root {
+        match value {
+            Value::Object(table) => {
+                for (name, value) in table {
+                    vars.insert(format!("{key}_{name}").to_uppercase(), to_var(value));
+                }
+            },
+            value => {
+                vars.insert(key.to_uppercase(), to_var(value));
+            },
+        }
+    }
+
+    Ok(vars)
+}
+
+fn to_var(value: Value) -> String {
+    match value {
+        Value::String(s) => s,
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
+
+fn required(errors: &mut Vec<String>, name: &str, value: &str) {
+    if value.trim().is_empty() {
+        errors.push(format!("{name}: is required"));
+    }
+}
+
+fn url(errors: &mut Vec<String>, name: &str, value: &str) {
+    if value.trim().is_empty() {
+        required(errors, name, value);
+    } else if Url::parse(value).is_err() {
+        errors.push(format!("{name}: \"{value}\" is not a valid URL"));
+    }
+}
+
+fn email(errors: &mut Vec<String>, name: &str, value: &str) {
+    if value.trim().is_empty() {
+        required(errors, name, value);
+    } else if !value.contains('@') {
+        errors.push(format!("{name}: \"{value}\" is not an email"));
+    }
+}
+
+fn custom_field(errors: &mut Vec<String>, name: &str, value: &str) {
+    let is_custom_field = value
+        .strip_prefix("customfield_")
+        .is_some_and(|id| !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()));
+
+    if value.trim().is_empty() {
+        required(errors, name, value);
+    } else if !is_custom_field {
+        errors.push(format!("{name}: \"{value}\" is not a Jira custom field ID (customfield_<number>)"));
+    }
 }
@@ -0,0 +1,19 @@
+use envconfig::Envconfig;
+
+#[derive(Envconfig, Clone)]
+pub struct Config {
+    /// Retries of a Jira or Graph request failed with a transient error.
+    #[envconfig(from = "HTTP_MAX_RETRIES", default = "5")]
+    pub(crate) max_retries: u32,
+    /// Delay before the first retry, doubled with every attempt and randomized by ±50%.
+    #[envconfig(from = "HTTP_BACKOFF_MS", default = "500")]
+    pub(crate) backoff_ms: u64,
+    #[envconfig(from = "HTTP_MAX_BACKOFF_SECS", default = "30")]
+    pub(crate) max_backoff_secs: u64,
+    /// Longer `Retry-After` is cut to this value.
+    #[envconfig(from = "HTTP_MAX_RETRY_AFTER_SECS", default = "120")]
+    pub(crate) max_retry_after_secs: u64,
+    /// Requests to the same host sent at once, 0 for no limit.
+    #[envconfig(from = "HTTP_MAX_CONCURRENCY_PER_HOST", default = "8")]
+    pub(crate) max_concurrency_per_host: usize,
+}
@@ -0,0 +1,2 @@
+pub(crate) mod cfg;
+pub mod model;
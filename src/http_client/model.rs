@@ -0,0 +1,179 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use envconfig::Envconfig;
+use rand::Rng;
+use reqwest::{header::RETRY_AFTER, Client, Request, RequestBuilder, Response, StatusCode};
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::{sleep, Duration, Instant},
+};
+use tracing::{info, warn};
+
+use crate::metrics::model::{record_http_request, record_retry};
+
+use super::cfg::Config;
+
+/// Target of outgoing HTTP request events, so they can be filtered separately (e.g. `http=warn`).
+const HTTP_TARGET: &str = "http";
+
+static HTTP_CLIENT: OnceLock<HttpClient> = OnceLock::new();
+
+/// Retry policy and per-host limits shared by every Jira and Graph request.
+pub struct HttpClient {
+    config: Config,
+    hosts: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+/// Sends Jira and Graph requests through the shared [`HttpClient`].
+pub(crate) trait ResilientRequest {
+    /// Sends the request, retrying transient failures:
+    /// - 429 responses and connection errors, after `Retry-After` or backoff;
+    /// - 5xx responses and timeouts of idempotent requests only, so issues and comments aren't created twice.
+    ///
+    /// Requests with streaming bodies (e.g. multipart uploads) are sent once. The last response is returned
+    /// as is, so error statuses are left to the caller.
+    async fn send_resilient(self) -> Result<Response>;
+}
+
+impl ResilientRequest for RequestBuilder {
+    async fn send_resilient(self) -> Result<Response> {
+        HttpClient::get().send(self).await
+    }
+}
+
+/// Sets the policy of all requests. Must be called before the first request, otherwise the defaults are used.
+pub fn init(config: Config) -> Result<()> {
+    HTTP_CLIENT
+        .set(HttpClient::new(config))
+        .map_err(|_| anyhow!("HTTP client is already initialized"))
+}
+
+impl HttpClient {
+    fn new(config: Config) -> Self {
+        Self { config, hosts: Mutex::new(HashMap::new()) }
+    }
+
+    fn get() -> &'static Self {
+        HTTP_CLIENT.get_or_init(|| {
+            Self::new(Config::init_from_hashmap(&HashMap::new()).expect("HTTP client defaults are valid"))
+        })
+    }
+
+    async fn send(&self, builder: RequestBuilder) -> Result<Response> {
+        let (client, request) = builder.build_split();
+        let mut request = request.context("Failed to build request")?;
+
+        let method = request.method().clone();
+        let host = request.url().host_str().unwrap_or_default().to_string();
+        let path = request.url().path().to_string();
+        let mut attempt = 0;
+
+        loop {
+            // The original is kept for the next attempt unless the body can't be copied
+            let (current, next) = match request.try_clone() {
+                Some(copy) if attempt < self.config.max_retries => (copy, Some(request)),
+                _ => (request, None),
+            };
+
+            let result = self.execute(&client, current, &host, &path).await;
+
+            let Some((next, (delay, reason))) = next.zip(self.retry_delay(&result, method.is_idempotent(), attempt)) else {
+                return result.with_context(|| format!("Failed to send {} request to {}{}", method, host, path));
+            };
+
+            warn!(target: HTTP_TARGET, %method, host, path, reason, attempt = attempt + 1, "HTTP request is retried in {:?}", delay);
+            record_retry(&host, reason);
+
+            sleep(delay).await;
+
+            request = next;
+            attempt += 1;
+        }
+    }
+
+    /// Sends a single attempt within the host limit, logging and measuring it.
+    /// Query is left out of the log, it may contain secrets.
+    async fn execute(&self, client: &Client, request: Request, host: &str, path: &str) -> reqwest::Result<Response> {
+        let method = request.method().clone();
+        let _permit = self.acquire(host).await;
+        let started = Instant::now();
+
+        let result = client.execute(request).await;
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        record_http_request(host, method.as_str(), path, result.as_ref().ok().map(|r| r.status().as_u16()), started);
+
+        match &result {
+            Ok(response) => {
+                info!(target: HTTP_TARGET, %method, host, path, status = response.status().as_u16(), latency_ms, "HTTP request");
+            },
+            Err(e) => {
+                warn!(target: HTTP_TARGET, %method, host, path, latency_ms, error = %e, "HTTP request failed");
+            },
+        }
+
+        result
+    }
+
+    async fn acquire(&self, host: &str) -> Option<OwnedSemaphorePermit> {
+        if self.config.max_concurrency_per_host == 0 {
+            return None;
+        }
+
+        let semaphore = self.hosts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(host.to_string())
+            .or_insert_with(|| Arc::new(Semaphore::new(self.config.max_concurrency_per_host)))
+            .clone();
+
+        // The semaphore is never closed
+        semaphore.acquire_owned().await.ok()
+    }
+
+    /// Delay and reason of the retry, `None` if the failure isn't transient or the request may have been processed.
+    fn retry_delay(&self, result: &reqwest::Result<Response>, idempotent: bool, attempt: u32) -> Option<(Duration, &'static str)> {
+        let backoff = self.backoff(attempt);
+
+        match result {
+            Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                Some((self.retry_after(response).unwrap_or(backoff), "throttled"))
+            },
+            Ok(response) if response.status().is_server_error() && idempotent => {
+                Some((self.retry_after(response).unwrap_or(backoff), "server_error"))
+            },
+            Ok(_) => None,
+            Err(e) if e.is_connect() => Some((backoff, "connection")),
+            Err(e) if e.is_timeout() && idempotent => Some((backoff, "timeout")),
+            Err(_) => None,
+        }
+    }
+
+    /// `Retry-After` in seconds or as HTTP date.
+    fn retry_after(&self, response: &Response) -> Option<Duration> {
+        let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+
+        let secs = match value.trim().parse::<u64>() {
+            Ok(secs) => secs,
+            Err(_) => {
+                let date = DateTime::parse_from_rfc2822(value).ok()?;
+                (date.with_timezone(&Utc) - Utc::now()).num_seconds().max(0) as u64
+            },
+        };
+
+        Some(Duration::from_secs(secs.min(self.config.max_retry_after_secs)))
+    }
+
+    /// Exponential backoff with jitter, so concurrent retries don't hit the API at once.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = Duration::from_millis(self.config.backoff_ms.saturating_mul(1 << attempt.min(16)))
+            .min(Duration::from_secs(self.config.max_backoff_secs));
+
+        delay.mul_f64(rand::rng().random_range(0.5..1.5))
+    }
+}
@@ -0,0 +1,11 @@
+use envconfig::Envconfig;
+
+#[derive(Envconfig, Clone)]
+pub struct Config {
+    /// Locale of messages when the Teams user has no preferred language or it's not translated.
+    #[envconfig(from = "DEFAULT_LOCALE", default = "ru")]
+    pub(crate) default_locale: String,
+    /// Folder with `<locale>.toml` catalogs, which add new locales or override built-in messages.
+    #[envconfig(from = "LOCALES_DIR", default = "")]
+    pub(crate) locales_dir: String,
+}
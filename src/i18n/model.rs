@@ -0,0 +1,116 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::{ensure, Context, Result};
+use serde_json::Value;
+
+use super::cfg::Config;
+
+/// Catalog for messages missing in the default locale.
+const FALLBACK_LOCALE: &str = "en";
+
+/// Built-in catalogs, `LOCALES_DIR` may add more.
+const BUILT_IN: &[(&str, &str)] = &[
+    ("en", include_str!("locales/en.toml")),
+    ("ru", include_str!("locales/ru.toml")),
+];
+
+/// Translations of user-facing messages. Nested tables are flattened to dotted keys,
+/// all keys are lowercase.
+pub struct Catalog {
+    default_locale: String,
+    locales: HashMap<String, HashMap<String, String>>,
+}
+
+impl Catalog {
+    pub fn new(config: Config) -> Result<Self> {
+        let mut locales: HashMap<String, HashMap<String, String>> = HashMap::new();
+
+        for (locale, content) in BUILT_IN {
+            locales.insert(locale.to_string(), parse(content).with_context(|| format!("Failed to parse {locale} catalog"))?);
+        }
+
+        if !config.locales_dir.is_empty() {
+            for entry in fs::read_dir(&config.locales_dir).with_context(|| format!("Failed to read {}", config.locales_dir))? {
+                let path = entry?.path();
+
+                if path.extension().is_none_or(|e| e != "toml") {
+                    continue;
+                }
+
+                let locale = normalize(&path.file_stem().unwrap_or_default().to_string_lossy());
+                let messages = read(&path).with_context(|| format!("Failed to read catalog {}", path.display()))?;
+
+                locales.entry(locale).or_default().extend(messages);
+            }
+        }
+
+        let default_locale = normalize(&config.default_locale);
+        ensure!(locales.contains_key(&default_locale), "No catalog for default locale {}", config.default_locale);
+
+        Ok(Self { default_locale, locales })
+    }
+
+    /// Message for the locale (e.g. Graph `preferredLanguage` like `en-US`). Falls back to the language
+    /// without region, then to the default locale, English and finally to the key itself.
+    pub(crate) fn get<'a>(&'a self, locale: Option<&str>, key: &'a str) -> &'a str {
+        self.find(locale, key).unwrap_or(key)
+    }
+
+    /// Same as [`Catalog::get`], but `None` if no catalog has the message.
+    pub(crate) fn find(&self, locale: Option<&str>, key: &str) -> Option<&str> {
+        let key = key.to_lowercase();
+
+        self.candidates(locale)
+            .into_iter()
+            .find_map(|l| self.locales.get(&l).and_then(|m| m.get(&key)))
+            .map(|m| m.as_str())
+    }
+
+    fn candidates(&self, locale: Option<&str>) -> Vec<String> {
+        let mut candidates = Vec::new();
+
+        if let Some(locale) = locale.map(normalize).filter(|l| !l.is_empty()) {
+            let language = locale.split('-').next().unwrap_or_default().to_string();
+            candidates.push(locale);
+            candidates.push(language);
+        }
+
+        candidates.push(self.default_locale.clone());
+        candidates.push(FALLBACK_LOCALE.to_string());
+        candidates
+    }
+}
+
+fn normalize(locale: &str) -> String {
+    locale.trim().replace('_', "-").to_lowercase()
+}
+
+fn read(path: &Path) -> Result<HashMap<String, String>> {
+    parse(&fs::read_to_string(path)?)
+}
+
+fn parse(content: &str) -> Result<HashMap<String, String>> {
+    let value: Value = toml::from_str(content)?;
+    let mut messages = HashMap::new();
+
+    flatten(&mut messages, "", value);
+
+    Ok(messages)
+}
+
+fn flatten(messages: &mut HashMap<String, String>, prefix: &str, value: Value) {
+    match value {
+        Value::Object(table) => {
+            for (key, value) in table {
+                let key = if prefix.is_empty() { key } else { format!("{prefix}.{key}") };
+                flatten(messages, &key, value);
+            }
+        },
+        Value::String(s) => {
+            messages.insert(prefix.to_lowercase(), s);
+        },
+        value => {
+            messages.insert(prefix.to_lowercase(), value.to_string());
+        },
+    }
+}
@@ -1,132 +1,217 @@
-use anyhow::{Context, Result};
-use regex::Regex;
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Context, Result};
 use reqwest::multipart::{Form, Part};
 use serde::Deserialize;
+use tracing::warn;
 
-use crate::{jira_api::model::JiraAPI, ms_graph_api::{
-    image::GraphApiImage, message::TeamsAttachment
-}};
+use crate::{
+    http_client::model::ResilientRequest,
+    jira_api::model::JiraAPI, 
+    ms_graph_api::{drive_item::DriveItem, image::GraphApiImage, message::TeamsAttachment}, 
+    server::AppStateShared,
+};
 
-use super::issue::Issue;
+use super::{adf::image_sources, issue::Issue};
 
+/// Part of attachment content URL, which is used for images in ADF documents.
+const CONTENT_PATH: &str = "/rest/api/3/attachment/content/";
 
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct JiraAttachment {
     pub(crate) id: String,
     pub(crate) filename: String,
+    pub(crate) mime_type: Option<String>,
+    #[serde(default)]
+    pub(crate) size: u64,
 }
 
-pub(crate) fn add_attachments_urls_to_description(description: &mut String, attachments: &Vec<TeamsAttachment>) -> bool {
-    let mut updated = false;
+impl JiraAttachment {
+    pub(crate) async fn get(jira_api: &JiraAPI, attachment_id: &str) -> Result<Self> {
+        jira_api.client
+            .get(format!("{}/rest/api/3/attachment/{}", jira_api.config.base_url, attachment_id))
+            .basic_auth(&jira_api.config.user, Some(&jira_api.config.token))
+            .send_resilient()
+            .await
+            .context("Failed to send get attachment request")?
+            .error_for_status()
+            .context("Get attachment request bad status")?
+            .json::<Self>()
+            .await
+            .context("Parse get attachment response")
+    }
 
-    for attachment in attachments {
-        if let (Some(content_url), Some(name)) = (&attachment.content_url, &attachment.name)
-            && !description.contains(content_url) 
-        {
-            description.push_str(format!("\n\n[{}|{}]", name, content_url).as_str());
-            updated = true;
-        }
+    pub(crate) async fn download(&self, jira_api: &JiraAPI) -> Result<Vec<u8>> {
+        let data = jira_api.client
+            .get(content_url(jira_api, &self.id))
+            .basic_auth(&jira_api.config.user, Some(&jira_api.config.token))
+            .send_resilient()
+            .await
+            .context("Failed to send download attachment request")?
+            .error_for_status()
+            .context("Download attachment request bad status")?
+            .bytes()
+            .await
+            .context("Failed to read attachment content")?;
+
+        Ok(data.to_vec())
     }
 
-    updated
+    pub(crate) fn is_image(&self) -> bool {
+        self.mime_type.as_deref().is_some_and(|t| t.starts_with("image/"))
+    }
 }
 
-pub(crate) async fn replace_images_in_description(
-    description: &mut String, 
-    graph_api_token: &str, 
-) -> Result<Vec<GraphApiImage>> {
-    let url_regex = Regex::new(r#"https://graph\.microsoft\.com/v1\.0/[^\s\|\]\\\"]*"#).unwrap();
-    let mut urls: Vec<_> = url_regex.find_iter(description)
-        .map(|mat| mat.as_str().to_string())
-        .collect();
-
-    let mut result: Vec<GraphApiImage> = Vec::new();
-
-    if !urls.is_empty() {
-        urls.sort();
-        urls.dedup();
-        
-        for url in urls {
-            if let Ok(img) = GraphApiImage::get(graph_api_token, &url).await {
-                *description = replace_img_tag_for_jira(description, &url, &img.name);
-                result.push(img);
-            }
+/// ID of the attachment from its content URL.
+pub(crate) fn attachment_id(url: &str) -> Option<&str> {
+    url.split(CONTENT_PATH)
+        .nth(1)
+        .map(|id| id.split(['?', '/']).next().unwrap_or(id))
+        .filter(|id| !id.is_empty())
+}
+
+/// Downloads inline images of Teams message.
+pub(crate) async fn get_images(html: &str, graph_api_token: &str) -> Vec<GraphApiImage> {
+    let mut result = Vec::new();
+
+    for url in image_sources(html) {
+        if let Ok(img) = GraphApiImage::get(graph_api_token, &url).await {
+            result.push(img);
         }
     }
 
-    Ok(result)
+    result
 }
 
+/// Uploads images missing in the issue and deletes old images which are not used anymore.
+/// Returns content URLs of the attachments by Teams image URLs.
 pub(crate) async fn replace_attachments(
     jira_api: &JiraAPI, 
     issue: &Issue, 
-    old_image_names: &Vec<String>, 
-    new_images: &Vec<GraphApiImage>
-) -> Result<()> {
-    let old_attachments = issue.get_attachments();
-
-    for old_image_name in old_image_names {
-        if !new_images.iter().any(|i| i.name == *old_image_name)
-            && let Some(attachments) = old_attachments.as_ref()
-            && let Some(attachment) = attachments.iter().find(|a| a.filename == *old_image_name) 
+    old_image_urls: &[String], 
+    new_images: &[GraphApiImage]
+) -> Result<HashMap<String, String>> {
+    let old_attachments = issue.get_attachments().unwrap_or_default();
+    let mut result = HashMap::new();
+
+    for image in new_images {
+        let attachment_id = match old_attachments.iter().find(|a| a.filename == image.name) {
+            Some(attachment) => Some(attachment.id.clone()),
+            None => upload_attachment(jira_api, issue, &image.name, image.data.clone(), &image.mime_str).await.ok(),
+        };
+
+        if let Some(attachment_id) = attachment_id {
+            result.insert(image.url.clone(), content_url(jira_api, &attachment_id));
+        }
+    }
+
+    for old_image_url in old_image_urls {
+        if !result.values().any(|url| url == old_image_url)
+            && let Some(attachment_id) = attachment_id(old_image_url)
+            && old_attachments.iter().any(|a| a.id == attachment_id)
         {
-            let _ = delete_attachment(jira_api, &attachment.id).await;
+            let _ = delete_attachment(jira_api, attachment_id).await;
         }
     }
 
-    for image in new_images {
-        if old_attachments.as_ref().is_none_or(|v| !v.iter().any(|a| a.filename == image.name)) {
-            let _ = upload_image(jira_api, issue, image).await;
+    Ok(result)
+}
+
+/// Copies Teams file attachments to the issue if `JIRA_UPLOAD_FILES` is set. Files are uploaded once per issue,
+/// too large files and files of not allowed types are skipped. Returns content URLs of the attachments by Teams URLs.
+pub(crate) async fn upload_files(
+    state_shared: &AppStateShared,
+    issue: &Issue,
+    attachments: &[TeamsAttachment],
+    graph_api_token: &str,
+) -> HashMap<String, String> {
+    let mut result = HashMap::new();
+
+    if !state_shared.jira.config.upload_files {
+        return result;
+    }
+
+    for attachment in attachments.iter().filter(|a| a.content_type.as_deref() == Some("reference")) {
+        let Some(url) = &attachment.content_url else {
+            continue;
+        };
+
+        match upload_file(state_shared, issue, url, graph_api_token).await {
+            Ok(Some(attachment_id)) => {
+                result.insert(url.clone(), content_url(&state_shared.jira, &attachment_id));
+            },
+            Ok(None) => (),
+            Err(e) => warn!("Failed to upload {} to issue {}: {:#}", url, issue.get_key(), e),
         }
     }
 
-    Ok(())
+    result
 }
 
-async fn upload_image(jira_api: &JiraAPI, issue: &Issue, image: &GraphApiImage) -> Result<()> {
-    let img_data = Part::bytes(image.data.clone())
-        .file_name(image.name.clone())
-        .mime_str(&image.mime_str)?;
+async fn upload_file(state_shared: &AppStateShared, issue: &Issue, url: &str, graph_api_token: &str) -> Result<Option<String>> {
+    let config = &state_shared.jira.config;
+    let item = DriveItem::get(graph_api_token, url).await?;
 
-    let form = Form::new().part("file", img_data);
+    if let Some(attachment_id) = state_shared.store.get_file(&item.id, &issue.get_id()).await?
+        && issue.get_attachments().unwrap_or_default().iter().any(|a| a.id == attachment_id)
+    {
+        return Ok(Some(attachment_id));
+    }
 
-    jira_api.client
-        .post(format!("{}/rest/api/2/issue/{}/attachments", jira_api.config.base_url, issue.get_id()))
-        .basic_auth(&jira_api.config.user, Some(&jira_api.config.token))
-        .header("X-Atlassian-Token", "no-check") // Add the X-Atlassian-Token header
-        .multipart(form)
-        .send()
-        .await
-        .context("Failed to send upload image request")?
-        .error_for_status()
-        .context("Upload image request bad status")?;
+    if config.upload_max_size_mb > 0 && item.size > config.upload_max_size_mb * 1024 * 1024 {
+        return Ok(None);
+    }
 
-    Ok(())
+    if !config.upload_mime_types.allows(item.mime_type()) {
+        return Ok(None);
+    }
+
+    let data = item.download(graph_api_token).await?;
+    let attachment_id = upload_attachment(&state_shared.jira, issue, &item.name, data, item.mime_type()).await?;
+
+    state_shared.store.set_file(&item.id, &issue.get_id(), &attachment_id).await?;
+
+    Ok(Some(attachment_id))
 }
 
+pub(crate) fn content_url(jira_api: &JiraAPI, attachment_id: &str) -> String {
+    format!("{}{}{}", jira_api.config.base_url, CONTENT_PATH, attachment_id)
+}
 
-fn replace_img_tag_for_jira(text: &str, search_url: &str, replace_with: &str) -> String {
-    // Escaping the target URL to safely insert it into the regex pattern
-    let escaped_url = regex::escape(search_url);
+async fn upload_attachment(jira_api: &JiraAPI, issue: &Issue, name: &str, data: Vec<u8>, mime_str: &str) -> Result<String> {
+    let file_data = Part::bytes(data)
+        .file_name(name.to_string())
+        .mime_str(mime_str)?;
 
-    // Crafting a regex pattern that matches an <img> tag containing the specific URL
-    // The pattern loosely captures an <img> tag to ensure flexibility in attribute ordering and spacing
-    let pattern = format!(r#"<img[^>]*?src\s*=\s*['"]?{}['"]?[^>]*?/?>"#, escaped_url);
+    let form = Form::new().part("file", file_data);
 
-    // Compiling the regex pattern
-    let re = Regex::new(&pattern).expect("Invalid regex pattern");
+    let attachments = jira_api.client
+        .post(format!("{}/rest/api/3/issue/{}/attachments", jira_api.config.base_url, issue.get_id()))
+        .basic_auth(&jira_api.config.user, Some(&jira_api.config.token))
+        .header("X-Atlassian-Token", "no-check") // Add the X-Atlassian-Token header
+        .multipart(form)
+        .send_resilient()
+        .await
+        .context("Failed to send upload attachment request")?
+        .error_for_status()
+        .context("Upload attachment request bad status")?
+        .json::<Vec<JiraAttachment>>()
+        .await
+        .context("Parse upload attachment response")?;
 
-    // Replacing the matched <img> tag(s) with an empty string
-    // You can replace "" with any other placeholder text if needed
-    re.replace_all(text, format!("\n\n!{}!\n\n", replace_with)).into_owned()
+    attachments
+        .into_iter()
+        .next()
+        .map(|a| a.id)
+        .ok_or(anyhow!("No attachment in upload response"))
 }
 
 async fn delete_attachment(jira_api: &JiraAPI, attachment_id: &str) -> Result<()> {
     jira_api.client
-        .delete(format!("{}/rest/api/2/attachment/{}", jira_api.config.base_url, attachment_id))
+        .delete(format!("{}/rest/api/3/attachment/{}", jira_api.config.base_url, attachment_id))
         .basic_auth(&jira_api.config.user, Some(&jira_api.config.token))
-        .send()
+        .send_resilient()
         .await
         .context("Failed to send delete attachment request")?
         .error_for_status()
@@ -134,23 +219,3 @@ async fn delete_attachment(jira_api: &JiraAPI, attachment_id: &str) -> Result<()
 
     Ok(())
 }
-
-pub(crate) fn find_old_attached_images(description: &str) -> Vec<String> {
-    let pattern_str = String::from("\n\n!+([^!]+)!\n\n");
-    let pattern = Regex::new(&pattern_str).unwrap();
-    
-    pattern
-        .find_iter(description)
-        .map(|mat| {
-                let chars = mat.as_str().chars().collect::<Vec<_>>();
-                if chars.len() > 6 {
-                    let trimmed_range = chars.iter().skip(3).take(chars.len() - 6);
-                    trimmed_range.collect()
-                } else {
-                    String::new()
-                }
-            }
-        )
-        .filter(|m| !m.is_empty())
-        .collect()
-}
\ No newline at end of file
@@ -1,4 +1,7 @@
+use std::{collections::HashMap, str::FromStr};
+
 use envconfig::Envconfig;
+use serde::Deserialize;
 
 #[derive(Envconfig, Clone)]
 pub struct Config {
@@ -16,4 +19,153 @@ pub struct Config {
     pub(crate) msteams_link_field_name: String,    
     #[envconfig(from = "JIRA_MSTEAMS_LINK_FIELD_JQL_NAME", default = "")]
     pub(crate) msteams_link_field_jql_name: String,    
+    /// Statuses of closed issues, comma separated or JSON array.
+    #[envconfig(from = "JIRA_FINAL_STATUSES", default = "Done,Rejected")]
+    pub(crate) final_statuses: StatusList,
+    /// JSON map of status name to notification template, overrides `[status]` table of the catalog.
+    #[envconfig(from = "JIRA_STATUS_TEMPLATES", default = "")]
+    pub(crate) status_templates: StatusTemplates,
+    /// Notification template for final statuses, `final_status` message of the catalog if empty.
+    #[envconfig(from = "JIRA_FINAL_STATUS_TEMPLATE", default = "")]
+    pub(crate) final_status_template: String,
+    /// Notification template for other statuses, `status_changed` message of the catalog if empty.
+    #[envconfig(from = "JIRA_STATUS_TEMPLATE", default = "")]
+    pub(crate) status_template: String,
+    /// `assignee_changed` message of the catalog if empty.
+    #[envconfig(from = "JIRA_ASSIGNEE_TEMPLATE", default = "")]
+    pub(crate) assignee_template: String,
+    /// Moves closed issues back to `JIRA_REOPEN_STATUS` on new replies in Teams.
+    #[envconfig(from = "JIRA_REOPEN_ENABLED", default = "false")]
+    pub(crate) reopen_enabled: bool,
+    #[envconfig(from = "JIRA_REOPEN_STATUS", default = "")]
+    pub(crate) reopen_status: String,
+    /// Days after resolution when issues may be reopened, 0 for no limit.
+    #[envconfig(from = "JIRA_REOPEN_WINDOW_DAYS", default = "14")]
+    pub(crate) reopen_window_days: u32,
+    /// Statuses when users are asked to confirm the fix with card buttons.
+    #[envconfig(from = "JIRA_CONFIRM_STATUSES", default = "Implementation/Test")]
+    pub(crate) confirm_statuses: StatusList,
+    /// Status of the issue confirmed as resolved.
+    #[envconfig(from = "JIRA_CONFIRMED_STATUS", default = "Done")]
+    pub(crate) confirmed_status: String,
+    /// Status of the issue whose fix didn't help.
+    #[envconfig(from = "JIRA_REJECTED_STATUS", default = "In Progress")]
+    pub(crate) rejected_status: String,
+    /// How deletion of Teams replies and Jira comments is mirrored to the other side.
+    #[envconfig(from = "SYNC_DELETE_MODE", default = "soft")]
+    pub(crate) delete_mode: DeleteMode,
+    /// Uploads Teams file attachments to Jira instead of linking them.
+    #[envconfig(from = "JIRA_UPLOAD_FILES", default = "false")]
+    pub(crate) upload_files: bool,
+    /// Larger files are linked, 0 for no limit.
+    #[envconfig(from = "JIRA_UPLOAD_MAX_SIZE_MB", default = "10")]
+    pub(crate) upload_max_size_mb: u64,
+    /// MIME types of uploaded files like `application/pdf` or `image/*`, comma separated or JSON array.
+    /// Any type is uploaded if empty.
+    #[envconfig(from = "JIRA_UPLOAD_MIME_TYPES", default = "")]
+    pub(crate) upload_mime_types: MimeTypes,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum DeleteMode {
+    /// Deletions are ignored.
+    Off,
+    /// Linked message is struck through and marked as deleted.
+    Soft,
+    /// Linked message is deleted.
+    Hard,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub(crate) struct StatusList(Vec<String>);
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct MimeTypes(Vec<String>);
+
+/// Status templates, status names are case-insensitive.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub(crate) struct StatusTemplates(HashMap<String, String>);
+
+impl StatusList {
+    pub(crate) fn contains(&self, status: &str) -> bool {
+        self.0.iter().any(|s| s.eq_ignore_ascii_case(status))
+    }
+}
+
+impl MimeTypes {
+    /// Whether the type is allowed, empty list allows everything.
+    pub(crate) fn allows(&self, mime_type: &str) -> bool {
+        let mime_type = mime_type.split(';').next().unwrap_or_default().trim();
+
+        self.0.is_empty() || self.0.iter().any(|pattern| match pattern.strip_suffix("/*") {
+            Some(prefix) => mime_type.split('/').next().is_some_and(|t| t.eq_ignore_ascii_case(prefix)),
+            None => pattern.eq_ignore_ascii_case(mime_type),
+        })
+    }
+}
+
+impl StatusTemplates {
+    pub(crate) fn get(&self, status: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(s, _)| s.to_lowercase() == status.to_lowercase())
+            .map(|(_, t)| t.as_str())
+    }
+}
+
+impl FromStr for StatusList {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_list(s).map(Self)
+    }
+}
+
+impl FromStr for MimeTypes {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_list(s).map(Self)
+    }
+}
+
+impl FromStr for StatusTemplates {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Ok(Self::default());
+        }
+
+        serde_json::from_str(s)
+    }
+}
+
+impl FromStr for DeleteMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "soft" => Ok(Self::Soft),
+            "hard" => Ok(Self::Hard),
+            _ => anyhow::bail!("Unknown delete mode: {s}"),
+        }
+    }
+}
+
+/// Comma separated list or JSON array.
+pub(crate) fn parse_list(s: &str) -> Result<Vec<String>, serde_json::Error> {
+    if s.trim_start().starts_with('[') {
+        return serde_json::from_str(s);
+    }
+
+    Ok(
+        s.split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect()
+    )
 }
@@ -2,10 +2,21 @@ use anyhow::{bail, Context, Result};
 use serde::Deserialize;
 use serde_json::{json, Value};
 
-use crate::{jira_api::model::JiraAPI, ms_graph_api::message::TeamsAttachment, server::AppStateShared};
+use crate::{
+    http_client::model::ResilientRequest,
+    jira_api::model::{is_not_found, JiraAPI}, 
+    metrics::model::record_synced,
+    ms_graph_api::{message::{TeamsAttachment, TeamsMention}, model::MsUser}, 
+    routes::model::Route, 
+    server::AppStateShared, 
+    store::model::{CommentLink, Store},
+};
 
 use super::{
-    attachment::{add_attachments_urls_to_description, find_old_attached_images, replace_attachments, replace_images_in_description}, 
+    adf::{attachment_links, doc, em, from_teams_html, media_urls, paragraph, paragraph_with_mention, strike_through, text},
+    attachment::{get_images, replace_attachments, upload_files}, 
+    mention::jira_accounts,
+    cfg::DeleteMode,
     issue::Issue, 
     model::JiraUser,
 };
@@ -17,8 +28,10 @@ const PROPERTY_KEY: &str = "teams_id";
 #[serde(rename_all = "camelCase")]
 pub(crate) struct JiraComment {
     pub(crate) id: String,
-    pub(crate) body: String,
+    /// ADF document, or wiki markup in webhooks.
+    pub(crate) body: Value,
     pub(crate) update_author: JiraUser,
+    pub(crate) author: Option<JiraUser>,
     pub(crate) properties: Option<Vec<JiraCommentProperty>>,
 }
 
@@ -37,32 +50,47 @@ impl JiraComment {
     #[allow(clippy::too_many_arguments)]
     pub(crate) async fn create_or_update (
         state_shared: AppStateShared,
+        route: &Route,
         description: &str, 
-        author_email: &str, 
-        attachments: &Vec<TeamsAttachment>,
+        author: &MsUser, 
+        attachments: &[TeamsAttachment],
+        mentions: &[TeamsMention],
         graph_api_token: &str,
         message_url: &str,
         reply_id: &str,
         message_id: &str,
     ) -> Result<Self> {
-        // let description = htmltoadf::convert_html_str_to_adf_str(description.clone());
-        // let description_json: Value = serde_json::from_str(description.as_str()).context("Failed to parse description JSON")?;
-        let mut description_v2 = html2md::parse_html(description);
+        let issue = match Issue::find(state_shared.clone(), route, message_url, message_id).await? {
+            Some(i) => i,
+            None => bail!("Issue not found"),
+        };
 
-        add_attachments_urls_to_description(&mut description_v2, attachments);
+        let comment = JiraComment::find(&state_shared.jira, &state_shared.store, route, &issue.get_id(), reply_id).await?;
+        let new_reply = comment.is_none();
 
-        let images = replace_images_in_description(&mut description_v2, graph_api_token).await?;
+        let images = get_images(description, graph_api_token).await;
+        let old_image_urls = comment.as_ref().map(|c| media_urls(&c.body)).unwrap_or_default();
+        let image_urls = replace_attachments(&state_shared.jira, &issue, &old_image_urls, &images).await?;
+        let file_urls = upload_files(&state_shared, &issue, attachments, graph_api_token).await;
+        let mentions = jira_accounts(&state_shared, graph_api_token, mentions).await;
 
         let author_id = state_shared.jira
-            .get_jira_user_by_email(author_email)
+            .get_jira_user_by_email(&author.mail)
             .await?
-            .map_or(author_email.to_string(), |u| u.account_id.clone());
-        
-        description_v2 = format!("On behalf of [~accountid:{}]:\n\n{}", author_id, description_v2);
-    
+            .map(|u| u.account_id);
+
+        let mut content = vec![paragraph_with_mention(
+            state_shared.i18n.get(None, "on_behalf_of"),
+            "author",
+            author_id.as_deref(),
+            &author.mail,
+        )];
+
+        content.extend(from_teams_html(description, &image_urls, &mentions));
+        content.extend(attachment_links(description, attachments, &file_urls));
 
         let payload = json!({
-            "body": description_v2.clone(),
+            "body": doc(content),
             "properties": [
                 {
                     "key": PROPERTY_KEY,
@@ -73,14 +101,6 @@ impl JiraComment {
             ]
         });
 
-        let issue = match Issue::find(state_shared.clone(), message_url, message_id).await? {
-            Some(i) => i,
-            None => bail!("Issue not found"),
-        };
-
-        let comment = JiraComment::find(&state_shared.jira, &issue.get_id(), reply_id).await?;
-        let comment_body = comment.as_ref().map(|com| com.body.clone()).unwrap_or_default();
-    
         let comment = match comment {
             Some(c) => {
                 c.update(&state_shared.jira, &issue.get_id(), &payload).await?;
@@ -88,10 +108,10 @@ impl JiraComment {
             },
             None => {
                  state_shared.jira.client
-                    .post(format!("{}/rest/api/2/issue/{}/comment", state_shared.jira.config.base_url, issue.get_id()))
+                    .post(format!("{}/rest/api/3/issue/{}/comment", state_shared.jira.config.base_url, issue.get_id()))
                     .basic_auth(&state_shared.jira.config.user, Some(&state_shared.jira.config.token))
                     .json(&payload)
-                    .send()
+                    .send_resilient()
                     .await
                     .context("Failed to send create comment request")?
                     .error_for_status()
@@ -101,14 +121,32 @@ impl JiraComment {
                     .context("Parse create comment response")?
             }
         };
-            
-        let old_image_names = find_old_attached_images(&comment_body);
-        replace_attachments(&state_shared.jira, &issue, &old_image_names, &images).await?;
+
+        state_shared.store
+            .set_comment(&CommentLink {
+                channel_id: route.channel_id.clone(),
+                reply_id: reply_id.to_string(),
+                message_id: message_id.to_string(),
+                issue_id: issue.get_id(),
+                comment_id: comment.id.clone(),
+            })
+            .await?;
+
+        record_synced("comment", new_reply);
+
+        issue.check_closed(&state_shared, route, message_id, author.preferred_language.as_deref(), new_reply).await?;
     
         Ok(comment)
     }
 
-    pub(crate) async fn find(jira_api: &JiraAPI, issue_id: &str, reply_id: &str) -> Result<Option<Self>> {
+    pub(crate) async fn find(jira_api: &JiraAPI, store: &Store, route: &Route, issue_id: &str, reply_id: &str) -> Result<Option<Self>> {
+        if let Some(link) = store.get_comment_by_reply_id(&route.channel_id, reply_id).await? {
+            match JiraComment::get(jira_api, &link.issue_id, &link.comment_id).await {
+                Ok(comment) => return Ok(Some(comment)),
+                Err(e) if is_not_found(&e) => store.remove_comment(&route.channel_id, reply_id).await?,
+                Err(e) => return Err(e),
+            }
+        }
 
         #[derive(Deserialize)]
         #[serde(rename_all = "camelCase")]
@@ -117,10 +155,10 @@ impl JiraComment {
         }
 
         let search_response = jira_api.client
-            .get(format!("{}/rest/api/2/issue/{}/comment", jira_api.config.base_url, issue_id))
+            .get(format!("{}/rest/api/3/issue/{}/comment", jira_api.config.base_url, issue_id))
             .basic_auth(&jira_api.config.user, Some(&jira_api.config.token))
             .query(&[("expand", "properties"), ("orderBy", "-created")])
-            .send()
+            .send_resilient()
             .await
             .context("Failed to get comments issue request")?
             .error_for_status()
@@ -148,12 +186,68 @@ impl JiraComment {
         Ok(result)
     }
 
+    /// Mirrors deletion of Teams reply to the linked comment according to `SYNC_DELETE_MODE`.
+    pub(crate) async fn delete_by_reply(
+        state_shared: AppStateShared,
+        route: &Route,
+        message_url: &str,
+        reply_id: &str,
+        message_id: &str,
+    ) -> Result<()> {
+        let delete_mode = state_shared.jira.config.delete_mode;
+
+        if delete_mode == DeleteMode::Off {
+            return Ok(());
+        }
+
+        let Some(issue) = Issue::find(state_shared.clone(), route, message_url, message_id).await? else {
+            return Ok(());
+        };
+
+        let Some(comment) = JiraComment::find(&state_shared.jira, &state_shared.store, route, &issue.get_id(), reply_id).await? else {
+            return Ok(());
+        };
+
+        match delete_mode {
+            DeleteMode::Hard => comment.delete(&state_shared.jira, &issue.get_id()).await?,
+            _ => {
+                let locale = state_shared.store.get_issue_locale(&route.channel_id, message_id).await?;
+                let notice = state_shared.i18n.get(locale.as_deref(), "deleted_in_teams");
+
+                let mut content = vec![paragraph(vec![text(notice, &[em()])])];
+                content.extend(strike_through(&comment.body)["content"].as_array().cloned().unwrap_or_default());
+
+                let payload = json!({
+                    "body": doc(content),
+                });
+
+                comment.update(&state_shared.jira, &issue.get_id(), &payload).await?
+            },
+        }
+
+        // Webhooks about comments of the service user aren't mirrored back, so the link can outlive the comment
+        state_shared.store.remove_comment(&route.channel_id, reply_id).await
+    }
+
+    pub(crate) async fn delete(&self, jira_api: &JiraAPI, issue_id: &str) -> Result<()> {
+        jira_api.client
+            .delete(format!("{}/rest/api/3/issue/{}/comment/{}", jira_api.config.base_url, issue_id, self.id))
+            .basic_auth(&jira_api.config.user, Some(&jira_api.config.token))
+            .send_resilient()
+            .await
+            .context("Failed to send delete comment request")?
+            .error_for_status()
+            .context("Delete comment request bad status")?;
+
+        Ok(())
+    }
+
     pub(crate) async fn update(&self, jira_api: &JiraAPI, issue_id: &str, payload: &Value) -> Result<()> {
         jira_api.client
-            .put(format!("{}/rest/api/2/issue/{}/comment/{}", jira_api.config.base_url, issue_id, self.id))
+            .put(format!("{}/rest/api/3/issue/{}/comment/{}", jira_api.config.base_url, issue_id, self.id))
             .basic_auth(&jira_api.config.user, Some(&jira_api.config.token))
             .json(&payload)
-            .send()
+            .send_resilient()
             .await
             .context("Failed to send update comment request")?
             .error_for_status()
@@ -162,6 +256,21 @@ impl JiraComment {
         Ok(())
     }
 
+    pub(crate) async fn get(jira_api: &JiraAPI, issue_id: &str, comment_id: &str) -> Result<Self> {
+        jira_api.client
+            .get(format!("{}/rest/api/3/issue/{}/comment/{}", jira_api.config.base_url, issue_id, comment_id))
+            .basic_auth(&jira_api.config.user, Some(&jira_api.config.token))
+            .query(&[("expand", "properties")])
+            .send_resilient()
+            .await
+            .context("Failed to send get comment request")?
+            .error_for_status()
+            .context("Get comment request bad status")?
+            .json::<JiraComment>()
+            .await
+            .context("Parse get comment response")
+    }
+
     // pub(crate) fn get_reply_id(&self) -> Option<String> {
     //     Some(
     //         self
@@ -182,7 +291,7 @@ impl JiraComment {
     //     });
 
     //     jira_api.client
-    //         .put(format!("{}/rest/api/2/comment/{}/properties/{}", jira_api.config.base_url, self.id, PROPERTY_KEY))
+    //         .put(format!("{}/rest/api/3/comment/{}/properties/{}", jira_api.config.base_url, self.id, PROPERTY_KEY))
     //         .basic_auth(&jira_api.config.user, Some(&jira_api.config.token))
     //         .json(&payload)
     //         .send()
@@ -193,21 +302,4 @@ impl JiraComment {
 
     //     Ok(())
     // }
-
-    // pub(crate) async fn get(jira_api: &JiraAPIShared, issue_id: &str, comment_id: &str) -> Result<Self> {
-    //     Ok(
-    //         jira_api.client
-    //             .get(format!("{}/rest/api/2/issue/{}/comment/{}", jira_api.config.base_url, issue_id, comment_id))
-    //             .basic_auth(&jira_api.config.user, Some(&jira_api.config.token))
-    //             .query(&[("expand", "properties")])
-    //             .send()
-    //             .await
-    //             .context("Failed to get comments issue request")?
-    //             .error_for_status()
-    //             .context("Get comments request bad status")?
-    //             .json::<JiraComment>()
-    //             .await
-    //             .context("Parse get comments response")?
-    //     )
-    // }
 }
@@ -1,9 +1,10 @@
-use adf2html::document::Document;
 use anyhow::{Context, Result};
 use serde::Deserialize;
-use serde_json::json;
+use serde_json::{json, Value};
 
-use super::model::JiraAPI;
+use crate::http_client::model::ResilientRequest;
+
+use super::model::{JiraAPI, JiraUser};
 
 const PROPERTY_KEY: &str = "teams_id";
 
@@ -12,8 +13,11 @@ const PROPERTY_KEY: &str = "teams_id";
 #[serde(rename_all = "camelCase")]
 pub(crate) struct JiraCommentV3 {
     pub(crate) id: String,
-    pub(crate) body: Document,
-    // pub(crate) update_author: JiraUser,
+    /// ADF document.
+    pub(crate) body: Value,
+    pub(crate) author: Option<JiraUser>,
+    pub(crate) update_author: Option<JiraUser>,
+    pub(crate) updated: Option<String>,
     pub(crate) properties: Option<Vec<JiraCommentProperty>>,
     pub(crate) rendered_body: String,
 }
@@ -93,7 +97,7 @@ impl JiraCommentV3 {
             .put(format!("{}/rest/api/3/comment/{}/properties/{}", jira_api.config.base_url, self.id, PROPERTY_KEY))
             .basic_auth(&jira_api.config.user, Some(&jira_api.config.token))
             .json(&payload)
-            .send()
+            .send_resilient()
             .await
             .context("Failed to send set property request")?
             .error_for_status()
@@ -102,12 +106,45 @@ impl JiraCommentV3 {
         Ok(())
     }
 
+    /// All comments of the issue, oldest first.
+    pub(crate) async fn list(jira_api: &JiraAPI, issue_id: &str) -> Result<Vec<Self>> {
+        #[derive(Deserialize)]
+        struct ListResponse {
+            comments: Vec<JiraCommentV3>,
+            total: usize,
+        }
+
+        let mut comments = Vec::new();
+
+        loop {
+            let response = jira_api.client
+                .get(format!("{}/rest/api/3/issue/{}/comment", jira_api.config.base_url, issue_id))
+                .basic_auth(&jira_api.config.user, Some(&jira_api.config.token))
+                .query(&[("expand", "properties,renderedBody"), ("startAt", &comments.len().to_string())])
+                .send_resilient()
+                .await
+                .context("Failed to send list comments request")?
+                .error_for_status()
+                .context("List comments request bad status")?
+                .json::<ListResponse>()
+                .await
+                .context("Parse list comments response")?;
+
+            let done = response.comments.is_empty();
+            comments.extend(response.comments);
+
+            if done || comments.len() >= response.total {
+                return Ok(comments);
+            }
+        }
+    }
+
     pub(crate) async fn get(jira_api: &JiraAPI, issue_id: &str, comment_id: &str) -> Result<Self> {
         jira_api.client
                 .get(format!("{}/rest/api/3/issue/{}/comment/{}", jira_api.config.base_url, issue_id, comment_id))
                 .basic_auth(&jira_api.config.user, Some(&jira_api.config.token))
                 .query(&[("expand", "properties,renderedBody")])
-                .send()
+                .send_resilient()
                 .await
                 .context("Failed to get comments issue request")?
                 .error_for_status()
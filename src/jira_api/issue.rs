@@ -1,14 +1,32 @@
-use anyhow::{anyhow, bail, Context, Result};
-use serde::{Deserialize, Deserializer};
-use serde_json::{json, Value};
+use std::collections::HashMap;
 
-use crate::{jira_api::model::JiraAPI, ms_graph_api::message::TeamsAttachment, server::AppStateShared};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::warn;
+
+use crate::{
+    http_client::model::ResilientRequest,
+    jira_api::model::{is_not_found, parse_date, JiraAPI}, 
+    metrics::model::record_synced,
+    ms_graph_api::{action::IssueAction, card::{CardAction, IssueCard}, message::{ReplyParts, TeamsAttachment, TeamsMention}, model::MsUser}, 
+    routes::model::Route, 
+    server::AppStateShared,
+    utils::{fill_html_template, fill_template},
+};
 
 use super::{
-    attachment::{add_attachments_urls_to_description, find_old_attached_images, replace_attachments, replace_images_in_description, JiraAttachment}, 
+    adf::{attachment_links, doc, from_teams_html, media_urls},
+    attachment::{get_images, replace_attachments, upload_files, JiraAttachment}, 
+    mention::jira_accounts,
     model::JiraUser,
+    transition::JiraTransition,
 };
 
+/// Kind of the bot reply with the issue card.
+const CARD_REPLY_KIND: &str = "card";
+
 
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -17,19 +35,25 @@ pub(crate) struct Issue {
     key: String,
     // #[serde(rename = "self")]
     // url: String,
-    #[serde(deserialize_with = "deserialize_issue_fields")]
     fields: Option<IssueFields>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
 struct IssueFields {
     attachment: Vec<JiraAttachment>,
-    description: Option<String>,
+    /// ADF document, or wiki markup in webhooks.
+    description: Option<Value>,
     assignee: Option<JiraUser>,
     // comment: IssueCommentField,
     status: IssueStatus,
-    // summary: String,
-    teams_link: Option<String>,
+    summary: Option<String>,
+    priority: Option<IssuePriority>,
+    project: Option<IssueProject>,
+    resolutiondate: Option<String>,
+    statuscategorychangedate: Option<String>,
+    /// Custom fields, the link to Teams message is one of them.
+    #[serde(flatten)]
+    other: serde_json::Map<String, Value>,
 }
 
 
@@ -43,6 +67,16 @@ struct IssueStatus {
     name: String,
 }
 
+#[derive(Clone, Debug, Deserialize)]
+struct IssuePriority {
+    name: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct IssueProject {
+    key: String,
+}
+
 impl Issue {
     pub(crate) fn get_id(&self) -> String {
         self.id.clone()
@@ -58,7 +92,7 @@ impl Issue {
             .map(|f| f.attachment.clone())
     }
     
-    pub(crate) fn get_description(&self) -> Option<String> {
+    pub(crate) fn get_description(&self) -> Option<Value> {
         self.fields
             .as_ref()
             .and_then(|f| f.description.clone())
@@ -70,16 +104,38 @@ impl Issue {
             .map(|f| f.status.name.clone())
     }
 
-    pub(crate) fn final_status(&self) -> bool {
+    pub(crate) fn get_summary(&self) -> Option<String> {
         self.fields
             .as_ref()
-            .is_some_and(|f| f.status.is_final())
+            .and_then(|f| f.summary.clone())
     }
 
-    pub(crate) fn get_teams_link(&self) -> Option<String> {
+    pub(crate) fn get_priority(&self) -> Option<String> {
         self.fields
             .as_ref()
-            .and_then(|f| f.teams_link.clone())
+            .and_then(|f| f.priority.as_ref().map(|p| p.name.clone()))
+    }
+
+    /// Resolution date, or the date of moving to the final status category for issues without resolution.
+    pub(crate) fn get_resolved_at(&self) -> Option<DateTime<Utc>> {
+        self.fields
+            .as_ref()
+            .and_then(|f| f.resolutiondate.as_ref().or(f.statuscategorychangedate.as_ref()))
+            .and_then(|d| parse_date(d))
+    }
+
+    pub(crate) fn get_project_key(&self) -> Option<String> {
+        self.fields
+            .as_ref()
+            .and_then(|f| f.project.as_ref().map(|p| p.key.clone()))
+    }
+
+    pub(crate) fn get_teams_link(&self, link_field_name: &str) -> Option<String> {
+        self.fields
+            .as_ref()
+            .and_then(|f| f.other.get(link_field_name))
+            .and_then(|v| v.as_str())
+            .map(|v| v.to_string())
     }
     
     pub(crate) fn get_assignee_name(&self) -> Option<String> {
@@ -95,10 +151,12 @@ impl Issue {
     #[allow(clippy::too_many_arguments)]
     pub(crate) async fn create_or_update (
         state_shared: AppStateShared,
+        route: &Route,
         summary: &str, 
         description: &str, 
-        reporter_email: &str, 
-        attachments: &Vec<TeamsAttachment>,
+        reporter: &MsUser, 
+        attachments: &[TeamsAttachment],
+        mentions: &[TeamsMention],
         graph_api_token: &str,
         message_url: &str,
         message_id: &str,
@@ -106,25 +164,21 @@ impl Issue {
         let mut summary = summary.to_string();
     
         if summary.is_empty() {
-            summary = format!("New issue from {reporter_email}");
+            summary = fill_template(state_shared.i18n.get(None, "new_issue_summary"), &[("reporter", &reporter.mail)]);
         }
-    
-        // let description = htmltoadf::convert_html_str_to_adf_str(description.clone());
-        // let description_json: Value = serde_json::from_str(description.as_str()).context("Failed to parse description JSON")?;
-        let mut description_v2 = html2md::parse_html(description);
 
-        add_attachments_urls_to_description(&mut description_v2, attachments);
-        let images = replace_images_in_description(&mut description_v2, graph_api_token).await?;
+        let images = get_images(description, graph_api_token).await;
+        let mentions = jira_accounts(&state_shared, graph_api_token, mentions).await;
     
         let mut payload = json!({
             "fields": {
                 "project": {
-                    "key": state_shared.jira.config.project_key
+                    "key": route.project_key
                 },
                 "summary": summary,
-                "description": description_v2.clone(),
+                "description": Issue::description_adf(description, attachments, &HashMap::new(), &HashMap::new(), &mentions),
                 "issuetype": {
-                    "name": "Task"
+                    "name": route.issue_type
                 }
             }
         });
@@ -135,10 +189,10 @@ impl Issue {
             "teams_link": message_url
         });
 
-        fields.insert(state_shared.jira.config.msteams_link_field_name.clone(), teams_link["teams_link"].clone());
+        fields.insert(route.link_field_name.clone(), teams_link["teams_link"].clone());
 
         let reporter_id = state_shared.jira
-            .get_jira_user_by_email(reporter_email).await?.map(|u| u.account_id)
+            .get_jira_user_by_email(&reporter.mail).await?.map(|u| u.account_id)
             .unwrap_or_default();
     
         if !reporter_id.is_empty() {
@@ -152,49 +206,241 @@ impl Issue {
         }
     
         
-        let mut maybe_issue = Issue::find(state_shared.clone(), message_url, message_id).await?;
+        let maybe_issue = Issue::find(state_shared.clone(), route, message_url, message_id).await?;
         let issue_exists = maybe_issue.is_some();
+
+        if let Some(issue) = &maybe_issue {
+            issue.check_closed(&state_shared, route, message_id, reporter.preferred_language.as_deref(), false).await?;
+        }
     
-        if !issue_exists {
-            #[derive(Deserialize)]
-            struct CreateIssueResponse {
-                id: String,
-            }
+        let issue = match maybe_issue {
+            Some(issue) => issue,
+            None => {
+                #[derive(Deserialize)]
+                struct CreateIssueResponse {
+                    id: String,
+                }
 
-            let result = state_shared.jira.client
-                        .post(format!("{}/rest/api/2/issue", state_shared.jira.config.base_url))
-                        .basic_auth(&state_shared.jira.config.user, Some(&state_shared.jira.config.token))
-                        .json(&payload)
-                        .send()
-                        .await
-                        .context("Failed to send create issue request")?
-                        .error_for_status()
-                        .context("Create request bad status")?
-                        .json::<CreateIssueResponse>()
-                        .await
-                        .context("Parse create issue response")?;
+                let result = state_shared.jira.client
+                            .post(format!("{}/rest/api/3/issue", state_shared.jira.config.base_url))
+                            .basic_auth(&state_shared.jira.config.user, Some(&state_shared.jira.config.token))
+                            .json(&payload)
+                            .send_resilient()
+                            .await
+                            .context("Failed to send create issue request")?
+                            .error_for_status()
+                            .context("Create request bad status")?
+                            .json::<CreateIssueResponse>()
+                            .await
+                            .context("Parse create issue response")?;
             
-            maybe_issue = Issue::get_issue(&state_shared.jira, &result.id).await.ok();
-        } else {
-            let issue = maybe_issue.as_mut().unwrap();
-            issue.update(&state_shared.jira, &payload).await?;
+                state_shared.store.set_issue_id(&route.channel_id, message_id, &result.id).await?;
+
+                Issue::get_issue(&state_shared.jira, &result.id).await.context("Failed to get created issue")?
+            },
+        };
+
+        if let Some(locale) = &reporter.preferred_language {
+            state_shared.store.set_issue_locale(&route.channel_id, message_id, locale).await?;
         }
-    
-        let issue = maybe_issue.ok_or(anyhow!("Failed to get created issue"))?;
 
-        let old_image_names = find_old_attached_images(&issue.get_description().unwrap_or_default());
+        // Images are attachments of the issue, so they are linked to the description once the issue exists
+        let old_image_urls = issue.get_description().as_ref().map(media_urls).unwrap_or_default();
+        let image_urls = replace_attachments(&state_shared.jira, &issue, &old_image_urls, &images).await.context("Failed to replace attachments")?;
+        let file_urls = upload_files(&state_shared, &issue, attachments, graph_api_token).await;
+
+        if issue_exists {
+            payload["fields"]["description"] = Issue::description_adf(description, attachments, &image_urls, &file_urls, &mentions);
+            issue.update(&state_shared.jira, &payload).await?;
+        } else if !image_urls.is_empty() || !file_urls.is_empty() {
+            let payload = json!({
+                "fields": {
+                    "description": Issue::description_adf(description, attachments, &image_urls, &file_urls, &mentions)
+                }
+            });
 
-        replace_attachments(&state_shared.jira, &issue, &old_image_names, &images).await.context("Failed to replace attachments")?;
+            issue.update(&state_shared.jira, &payload).await?;
+        }
+
+        record_synced("issue", !issue_exists);
     
         Ok((issue, issue_exists))
     }
 
+    fn description_adf(
+        html: &str,
+        attachments: &[TeamsAttachment],
+        image_urls: &HashMap<String, String>,
+        file_urls: &HashMap<String, String>,
+        mentions: &HashMap<String, String>,
+    ) -> Value {
+        let mut content = from_teams_html(html, image_urls, mentions);
+        content.extend(attachment_links(html, attachments, file_urls));
+        doc(content)
+    }
+
     pub(crate) async fn find(
         state_shared: AppStateShared, 
+        route: &Route,
         teams_url: &str,
         message_id: &str,
     ) -> Result<Option<Self>> {
-        let jql = format!("project = \"{}\" AND \"{}\" = \"{}\"", state_shared.jira.config.project_key, state_shared.jira.config.msteams_link_field_jql_name, teams_url);
+        let issue = match Issue::find_in_store(&state_shared, route, message_id).await? {
+            Some(i) => i,
+            None => match Issue::find_by_teams_url(&state_shared, route, teams_url).await? {
+                Some(i) => {
+                    state_shared.store.set_issue_id(&route.channel_id, message_id, &i.id).await?;
+                    i
+                },
+                None => return Ok(None),
+            },
+        };
+
+        Ok(Some(issue))
+    }
+
+    /// Warns the thread if the issue is closed. A new reply reopens the issue instead,
+    /// if it's enabled for the route and the issue was resolved within the reopen window.
+    pub(crate) async fn check_closed(
+        &self,
+        state_shared: &AppStateShared,
+        route: &Route,
+        message_id: &str,
+        locale: Option<&str>,
+        new_reply: bool,
+    ) -> Result<()> {
+        let config = &state_shared.jira.config;
+
+        if !self.get_status().is_some_and(|s| route.is_final_status(config, &s)) {
+            return Ok(());
+        }
+
+        let reopen_status = route
+            .reopen_status(config)
+            .filter(|_| new_reply)
+            .filter(|_| config.reopen_window_days == 0 || self.get_resolved_at().is_some_and(|d| {
+                d + Duration::days(config.reopen_window_days.into()) >= Utc::now()
+            }));
+
+        if let Some(status) = reopen_status
+            && JiraTransition::to_status(&state_shared.jira, &self.id, status).await.context("Failed to reopen issue")?
+        {
+            let url = format!("{}/browse/{}", config.base_url, self.key);
+            let reply_body = fill_html_template(state_shared.i18n.get(locale, "issue_reopened"), &[("key", &self.key), ("url", &url), ("status", status)]);
+
+            state_shared.microsoft
+                .reply_to_issue(route, message_id, &reply_body, &ReplyParts::default())
+                .await?;
+        } else {
+            state_shared.microsoft
+                .reply_to_issue(route, message_id, state_shared.i18n.get(locale, "issue_closed"), &ReplyParts::default())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Shows the issue card in the thread, updating the posted one, with the notice of the last change.
+    /// Without cards, or if the card can't be posted, the notice is posted as HTML reply,
+    /// the link to the issue is posted for a new issue. The HTML reply is kept as bot reply of `reply_kind`
+    /// if set, so it's edited instead of posted again when the event is retried.
+    pub(crate) async fn notify(
+        &self,
+        state_shared: &AppStateShared,
+        route: &Route,
+        message_id: &str,
+        locale: Option<&str>,
+        notice: Option<&str>,
+        reply_kind: Option<&str>,
+    ) -> Result<()> {
+        let url = format!("{}/browse/{}", state_shared.jira.config.base_url, self.key);
+
+        if state_shared.microsoft.config.cards_enabled {
+            let status = self.get_status().unwrap_or_default();
+            let summary = self.get_summary().unwrap_or_default();
+            let assignee = self.get_assignee_name();
+            let priority = self.get_priority();
+
+            let card = IssueCard {
+                key: &self.key,
+                url: &url,
+                summary: &summary,
+                is_final: route.is_final_status(&state_shared.jira.config, &status),
+                status: &status,
+                assignee: assignee.as_deref(),
+                priority: priority.as_deref(),
+                notice,
+                actions: self.card_actions(state_shared, locale, &status),
+            };
+
+            let (reply_body, attachment) = card.to_reply(&state_shared.i18n, locale);
+            let parts = ReplyParts { attachments: vec![attachment], ..Default::default() };
+
+            match state_shared.microsoft
+                .upsert_bot_reply(&state_shared.store, route, message_id, CARD_REPLY_KIND, &reply_body, &parts)
+                .await
+            {
+                Ok(()) => return Ok(()),
+                Err(e) => warn!("Failed to post card of {}, falling back to HTML: {:#}", self.key, e),
+            }
+        }
+
+        let reply_body = match notice {
+            Some(notice) => notice.to_string(),
+            None => format!("<a href=\"{0}\">{0}</a>", url),
+        };
+
+        match reply_kind {
+            Some(kind) => {
+                state_shared.microsoft
+                    .upsert_bot_reply(&state_shared.store, route, message_id, kind, &reply_body, &ReplyParts::default())
+                    .await?;
+            },
+            None => {
+                state_shared.microsoft
+                    .reply_to_issue(route, message_id, &reply_body, &ReplyParts::default())
+                    .await?;
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Buttons confirming or rejecting the fix, shown in `JIRA_CONFIRM_STATUSES`.
+    fn card_actions(&self, state_shared: &AppStateShared, locale: Option<&str>, status: &str) -> Vec<CardAction> {
+        if !state_shared.jira.config.confirm_statuses.contains(status) {
+            return Vec::new();
+        }
+
+        IssueAction::ALL
+            .iter()
+            .filter_map(|action| {
+                action.url(&state_shared.microsoft.config, &self.id).map(|url| CardAction {
+                    title: state_shared.i18n.get(locale, action.title_key()).to_string(),
+                    url,
+                })
+            })
+            .collect()
+    }
+
+    async fn find_in_store(state_shared: &AppStateShared, route: &Route, message_id: &str) -> Result<Option<Self>> {
+        let Some(issue_id) = state_shared.store.get_issue_id(&route.channel_id, message_id).await? else {
+            return Ok(None);
+        };
+
+        match Issue::get_issue(&state_shared.jira, &issue_id).await {
+            Ok(issue) => Ok(Some(issue)),
+            Err(e) if is_not_found(&e) => {
+                // Issue was deleted, let the search find it again
+                state_shared.store.remove_issue_id(&route.channel_id, message_id).await?;
+                Ok(None)
+            },
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn find_by_teams_url(state_shared: &AppStateShared, route: &Route, teams_url: &str) -> Result<Option<Self>> {
+        let jql = format!("project = \"{}\" AND \"{}\" = \"{}\"", route.project_key, route.link_field_jql_name, teams_url);
 
         #[derive(Deserialize)]
         struct SearchResponse {
@@ -202,10 +448,10 @@ impl Issue {
         }
 
         let result = state_shared.jira.client
-            .get(format!("{}/rest/api/2/search/jql", state_shared.jira.config.base_url))
+            .get(format!("{}/rest/api/3/search/jql", state_shared.jira.config.base_url))
             .basic_auth(&state_shared.jira.config.user, Some(&state_shared.jira.config.token))
             .query(&[("maxResults", "1"), ("jql", &jql), ("fields", "*all")])
-            .send()
+            .send_resilient()
             .await
             .context("Failed to send search issue request")?;
 
@@ -224,15 +470,53 @@ impl Issue {
             return Ok(None)
         }
 
-        let issue = response.issues.pop().unwrap();
+        Ok(response.issues.pop())
+    }
 
-        if issue.clone().fields.is_some_and(|i| i.status.is_final()) {
-            state_shared.microsoft
-                .reply_to_issue(message_id, &String::from("Извините, но данная задача закрыта. Просим вас завести новую, иначе мы можем пропустить это сообщение"))
-                .await?;
+    /// Issues of the route linked to Teams messages and updated within the last hours.
+    pub(crate) async fn search_updated(state_shared: &AppStateShared, route: &Route, hours: u32) -> Result<Vec<Self>> {
+        let jql = format!(
+            "project = \"{}\" AND \"{}\" IS NOT EMPTY AND updated >= \"-{}h\" ORDER BY updated",
+            route.project_key, route.link_field_jql_name, hours,
+        );
+
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct SearchResponse {
+            issues: Vec<Issue>,
+            next_page_token: Option<String>,
         }
 
-        Ok(Some(issue))
+        let mut issues = Vec::new();
+        let mut page_token = None;
+
+        loop {
+            let mut query = vec![("maxResults", "100"), ("jql", jql.as_str()), ("fields", "*all")];
+
+            if let Some(token) = page_token.as_deref() {
+                query.push(("nextPageToken", token));
+            }
+
+            let response = state_shared.jira.client
+                .get(format!("{}/rest/api/3/search/jql", state_shared.jira.config.base_url))
+                .basic_auth(&state_shared.jira.config.user, Some(&state_shared.jira.config.token))
+                .query(&query)
+                .send_resilient()
+                .await
+                .context("Failed to send search updated issues request")?
+                .error_for_status()
+                .context("Search updated issues request bad status")?
+                .json::<SearchResponse>()
+                .await
+                .context("Parse search updated issues response")?;
+
+            issues.extend(response.issues);
+
+            match response.next_page_token {
+                Some(token) => page_token = Some(token),
+                None => return Ok(issues),
+            }
+        }
     }
 
     pub(crate) async fn get_issue(
@@ -240,9 +524,9 @@ impl Issue {
         issue_id: &str,
     ) -> Result<Self> {
         let issue = jira_api.client
-            .get(format!("{}/rest/api/2/issue/{}", jira_api.config.base_url, issue_id))
+            .get(format!("{}/rest/api/3/issue/{}", jira_api.config.base_url, issue_id))
             .basic_auth(&jira_api.config.user, Some(&jira_api.config.token))
-            .send()
+            .send_resilient()
             .await
             .context("Failed to send get issue request")?
             .error_for_status()
@@ -256,10 +540,10 @@ impl Issue {
 
     pub(crate) async fn update(&self, jira_api: &JiraAPI, payload: &Value) -> Result<()> {
         jira_api.client
-            .put(format!("{}/rest/api/2/issue/{}", jira_api.config.base_url, self.id))
+            .put(format!("{}/rest/api/3/issue/{}", jira_api.config.base_url, self.id))
             .basic_auth(&jira_api.config.user, Some(&jira_api.config.token))
             .json(&payload)
-            .send()
+            .send_resilient()
             .await
             .context("Failed to send issue update request")?
             .error_for_status()
@@ -269,34 +553,3 @@ impl Issue {
     }
 }
 
-impl IssueStatus {
-    pub(crate) fn is_final(&self) -> bool {
-        self.name.to_lowercase() == "Done".to_lowercase() || self.name.to_lowercase() == "Rejected".to_lowercase()
-    }
-}
-
-fn deserialize_issue_fields<'de, D>(deserializer: D) -> Result<Option<IssueFields>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let env_var_name = std::env::var("JIRA_MSTEAMS_LINK_FIELD_NAME").unwrap_or(String::from("teamsLink"));
-    let v: Value = Deserialize::deserialize(deserializer)?;
-
-    if let Some(map) = v.as_object() {
-        let mut fields_map = serde_json::Map::new();
-        for (k, v) in map {
-            // For every key in the JSON, if it matches the dynamic `teams_link` field,
-            // remap it to "teams_link". Otherwise, use the key as is.
-            if k == &env_var_name {
-                fields_map.insert("teams_link".to_string(), v.clone());
-            } else {
-                fields_map.insert(k.clone(), v.clone());
-            }
-        }
-        let fields: IssueFields = serde_json::from_value(Value::Object(fields_map))
-            .map_err(serde::de::Error::custom)?;
-        Ok(Some(fields))
-    } else {
-        Err(serde::de::Error::custom("expected a map for fields"))
-    }
-}
\ No newline at end of file
@@ -0,0 +1,44 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::http_client::model::ResilientRequest;
+
+use super::model::JiraAPI;
+
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct JiraIssueV3 {
+    pub(crate) fields: JiraIssueV3Fields,
+    pub(crate) rendered_fields: Option<JiraIssueV3RenderedFields>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct JiraIssueV3Fields {
+    pub(crate) summary: String,
+    /// ADF document.
+    pub(crate) description: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct JiraIssueV3RenderedFields {
+    pub(crate) description: Option<String>,
+}
+
+impl JiraIssueV3 {
+    pub(crate) async fn get(jira_api: &JiraAPI, issue_id: &str) -> Result<Self> {
+        jira_api.client
+            .get(format!("{}/rest/api/3/issue/{}", jira_api.config.base_url, issue_id))
+            .basic_auth(&jira_api.config.user, Some(&jira_api.config.token))
+            .query(&[("fields", "summary,description"), ("expand", "renderedFields")])
+            .send_resilient()
+            .await
+            .context("Failed to send get issue request")?
+            .error_for_status()
+            .context("Get issue request bad status")?
+            .json::<Self>()
+            .await
+            .context("Parse get issue response")
+    }
+}
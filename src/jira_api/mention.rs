@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use regex::{Captures, Regex};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    ms_graph_api::message::{MentionedIdentity, MentionedUser, TeamsMention},
+    server::AppStateShared,
+    utils::html_escape,
+};
+
+/// Jira account IDs of users mentioned in Teams message, by IDs of `<at>` tags.
+/// Users without Jira account are left out, so they stay plain text.
+pub(crate) async fn jira_accounts(state_shared: &AppStateShared, graph_api_token: &str, mentions: &[TeamsMention]) -> HashMap<String, String> {
+    let mut result = HashMap::new();
+
+    for mention in mentions {
+        let Some(user_id) = mention.mentioned.user.as_ref().and_then(|u| Uuid::try_parse(&u.id).ok()) else {
+            continue;
+        };
+
+        let Ok(user) = state_shared.microsoft.get_user(graph_api_token, user_id).await else {
+            continue;
+        };
+
+        if let Ok(Some(jira_user)) = state_shared.jira.get_jira_user_by_email(&user.mail).await {
+            result.insert(mention.id.to_string(), jira_user.account_id);
+        }
+    }
+
+    result
+}
+
+/// Replaces placeholders of [`replace_mentions`] in the rendered HTML with `<at>` tags.
+pub(crate) fn insert_mentions(html: &str, mentions: &[TeamsMention]) -> String {
+    // Mention is rendered as a span with the placeholder text
+    let placeholder = Regex::new(r"<span[^>]*>@@mention(\d+)@@</span>").unwrap();
+
+    placeholder
+        .replace_all(html, |caps: &Captures| {
+            let id = caps[1].parse::<u32>().unwrap_or_default();
+            let text = mentions.iter().find(|m| m.id == id).map(|m| m.mention_text.as_str()).unwrap_or_default();
+
+            format!("<at id=\"{}\">{}</at>", id, html_escape(text))
+        })
+        .into_owned()
+}
+
+/// Puts placeholders into mention nodes with Teams users, collecting the users.
+pub(crate) async fn replace_mentions(
+    state_shared: &AppStateShared,
+    node: &mut Value,
+    mentions: &mut Vec<TeamsMention>,
+    users: &mut HashMap<String, Option<MentionedUser>>,
+) -> Result<()> {
+    if node["type"] == "mention"
+        && let Some(account_id) = node["attrs"]["id"].as_str().map(|id| id.to_string())
+    {
+        if !users.contains_key(&account_id) {
+            users.insert(account_id.clone(), teams_user(state_shared, &account_id).await?);
+        }
+
+        if let Some(user) = users.get(&account_id).cloned().flatten() {
+            let id = mentions.len() as u32;
+
+            mentions.push(TeamsMention {
+                id,
+                mention_text: user.display_name.clone().unwrap_or_default(),
+                mentioned: MentionedIdentity { user: Some(user) },
+            });
+
+            node["attrs"]["text"] = format!("@@mention{id}@@").into();
+        }
+    }
+
+    if let Some(content) = node.get_mut("content").and_then(|c| c.as_array_mut()) {
+        for child in content {
+            Box::pin(replace_mentions(state_shared, child, mentions, users)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Teams user with the same email as the Jira user.
+async fn teams_user(state_shared: &AppStateShared, account_id: &str) -> Result<Option<MentionedUser>> {
+    let jira_user = state_shared.jira.find_user_by_id(account_id).await?;
+
+    // Email is hidden by the privacy settings of the user
+    let Some(email) = jira_user.email_address.as_deref().filter(|e| !e.is_empty()) else {
+        return Ok(None);
+    };
+
+    let token = state_shared.microsoft.get_token().await?;
+
+    let user = state_shared.microsoft
+        .find_user_by_email(&token, email)
+        .await?
+        .map(|user| MentionedUser {
+            id: user.id.to_string(),
+            display_name: user.display_name.or(jira_user.display_name).or(Some(user.mail)),
+            user_identity_type: Some("aadUser".to_string()),
+        });
+
+    Ok(user)
+}
@@ -1,6 +1,11 @@
+pub(crate) mod adf;
 pub(crate) mod attachment;
 pub(crate) mod cfg;
 pub(crate) mod comment;
 pub(crate) mod comment_v3;
 pub(crate) mod issue;
+pub(crate) mod issue_v3;
+pub(crate) mod mention;
 pub mod model;
+pub(crate) mod render;
+pub(crate) mod transition;
@@ -1,12 +1,15 @@
 use anyhow::{bail, Context as _, Result};
-use reqwest::Client;
+use chrono::{DateTime, Utc};
+use reqwest::{Client, StatusCode};
 use serde::Deserialize;
-use tokio::sync::RwLock;
+use tokio::{sync::RwLock, time::{Duration, Instant}};
 
-use crate::utils::get_reqwest_client;
+use crate::{http_client::model::ResilientRequest, metrics::model::record_jira_user_lookup, utils::get_reqwest_client};
 
 use super::cfg::Config;
 
+const JIRA_DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f%z";
+
 
 pub struct JiraAPI {
     pub(crate) config: Config,
@@ -57,12 +60,28 @@ impl JiraAPI {
         }
     }
 
+    /// Checks credentials with the cheapest authenticated request. It isn't retried, so probes stay fast.
+    pub(crate) async fn get_myself(&self, timeout: Duration) -> Result<JiraUser> {
+        self.client
+            .get(format!("{}/rest/api/3/myself", self.config.base_url))
+            .basic_auth(&self.config.user, Some(&self.config.token))
+            .timeout(timeout)
+            .send()
+            .await
+            .context("Failed to send get myself request")?
+            .error_for_status()
+            .context("Get myself request bad status")?
+            .json::<JiraUser>()
+            .await
+            .context("Parse get myself response")
+    }
+
     async fn get_user_from_api_by_id(&self, id: &str) -> Result<JiraUser> {
         let result = self.client
             .get(format!("{}/rest/api/2/user", self.config.base_url))
             .basic_auth(&self.config.user, Some(&self.config.token))
             .query(&[("accountId", id)])
-            .send()
+            .send_resilient()
             .await
             .context("Failed to send get user email request")?
             .error_for_status()
@@ -101,14 +120,15 @@ impl JiraAPI {
     }
 
     async fn get_user_from_api_by_email(&self, email: &str) -> Result<Option<JiraUser>> {
+        let started = Instant::now();
         let mut page = 0;
-        
+
         loop {
             let result = self.client
                 .get(format!("{}/rest/api/3/users", self.config.base_url))
                 .query(&[("startAt", page * 50), ("maxResults", 50)])
                 .basic_auth(&self.config.user, Some(&self.config.token))
-                .send()
+                .send_resilient()
                 .await
                 .context("Failed to send get reporter request")?;
 
@@ -133,12 +153,30 @@ impl JiraAPI {
                 .cloned();
     
             if reporter.is_some() {
+                record_jira_user_lookup(page + 1, started);
                 return Ok(reporter);
             };
     
             page += 1;
         }
 
+        record_jira_user_lookup(page + 1, started);
+
         Ok(None)
     }
 }
+
+/// Checks whether the error was caused by a 404 response of Jira API.
+pub(crate) fn is_not_found(err: &anyhow::Error) -> bool {
+    err
+        .downcast_ref::<reqwest::Error>()
+        .and_then(|e| e.status())
+        .is_some_and(|s| s == StatusCode::NOT_FOUND)
+}
+
+/// Parses dates like `2024-05-01T10:00:00.000+0300` returned by Jira.
+pub(crate) fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_str(date, JIRA_DATE_FORMAT)
+        .ok()
+        .map(|d| d.with_timezone(&Utc))
+}
@@ -0,0 +1,162 @@
+use std::collections::{HashMap, VecDeque};
+
+use adf2html::document::Document;
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chrono_tz::Tz;
+use regex::Regex;
+use serde_json::{json, Value};
+use tracing::warn;
+
+use crate::{
+    ms_graph_api::{drive_item::DriveItem, message::{HostedContent, ReplyParts}},
+    routes::model::Route,
+    server::AppStateShared,
+    utils::html_escape,
+};
+
+use super::{
+    attachment::{attachment_id, content_url, JiraAttachment},
+    mention::{insert_mentions, replace_mentions},
+};
+
+/// Graph API limit for images posted along with a message, larger images go as files.
+const MAX_HOSTED_CONTENT_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Jira comment or description to be posted in the issue thread.
+pub(crate) struct TeamsReply<'a> {
+    pub(crate) route: &'a Route,
+    pub(crate) issue_key: &'a str,
+    /// ADF document.
+    pub(crate) body: &'a Value,
+    /// HTML rendered by Jira, the only place with IDs of attachments shown by the document.
+    pub(crate) rendered_body: &'a str,
+    /// Link for the content which can't be rendered.
+    pub(crate) link: &'a str,
+}
+
+impl TeamsReply<'_> {
+    /// Renders the document to Teams HTML. Mentions of Jira users with Teams account become Teams mentions,
+    /// attached images are posted along with the reply and other files are copied to the channel's SharePoint folder.
+    pub(crate) async fn render(&self, state_shared: &AppStateShared, timezone: Option<Tz>) -> Result<(String, ReplyParts)> {
+        let mut body = self.body.clone();
+        let mut parts = ReplyParts::default();
+
+        let mut rendered_ids: VecDeque<String> = Regex::new(r"/rest/api/3/attachment/content/(\d+)")
+            .unwrap()
+            .captures_iter(self.rendered_body)
+            .map(|caps| caps[1].to_string())
+            .collect();
+
+        let mut media = Vec::new();
+        replace_media(&mut body, &mut rendered_ids, &mut media);
+
+        replace_mentions(state_shared, &mut body, &mut parts.mentions, &mut HashMap::new()).await?;
+
+        let document = serde_json::from_value::<Document>(body).context("Failed to parse document")?;
+        let mut html = insert_mentions(&document.to_html(timezone, self.link), &parts.mentions);
+
+        for (i, attachment_id) in media.iter().enumerate() {
+            let replacement = match attachment_id {
+                Some(attachment_id) => self.forward_attachment(state_shared, attachment_id, &mut parts)
+                    .await
+                    .unwrap_or_else(|e| {
+                        warn!("Failed to forward attachment {} of {}: {:#}", attachment_id, self.issue_key, e);
+                        let url = content_url(&state_shared.jira, attachment_id);
+                        format!("<a href=\"{0}\">{0}</a>", html_escape(&url))
+                    }),
+                None => String::new(),
+            };
+
+            html = html.replace(&media_placeholder(i), &replacement);
+        }
+
+        Ok((html, parts))
+    }
+
+    /// Copies Jira attachment to Teams and returns HTML referencing it.
+    async fn forward_attachment(&self, state_shared: &AppStateShared, attachment_id: &str, parts: &mut ReplyParts) -> Result<String> {
+        let attachment = JiraAttachment::get(&state_shared.jira, attachment_id).await?;
+        let data = attachment.download(&state_shared.jira).await?;
+
+        if attachment.is_image() && attachment.size <= MAX_HOSTED_CONTENT_SIZE {
+            let temporary_id = (parts.hosted_contents.len() + 1).to_string();
+            let html = format!("<img src=\"../hostedContents/{}/$value\" alt=\"{}\">", temporary_id, html_escape(&attachment.filename));
+
+            parts.hosted_contents.push(HostedContent {
+                temporary_id,
+                content_bytes: STANDARD.encode(data),
+                content_type: attachment.mime_type.unwrap_or_default(),
+            });
+
+            return Ok(html);
+        }
+
+        let token = state_shared.microsoft.get_token().await?;
+        let item = DriveItem::upload_to_channel(&token, self.route, &[self.issue_key, &attachment.filename], data).await?;
+        let teams_attachment = item.to_attachment().context("No eTag of uploaded file")?;
+        let html = format!("<attachment id=\"{}\"></attachment>", teams_attachment.id.as_deref().unwrap_or_default());
+
+        parts.attachments.push(teams_attachment);
+
+        Ok(html)
+    }
+}
+
+fn media_placeholder(index: usize) -> String {
+    format!("@@media{index}@@")
+}
+
+/// Replaces media nodes with placeholders, collecting IDs of their attachments. File media get IDs
+/// from the rendered HTML in order, external media (images from Teams) have them in the URL.
+pub(super) fn replace_media(node: &mut Value, rendered_ids: &mut VecDeque<String>, media: &mut Vec<Option<String>>) {
+    let Some(content) = node.get_mut("content").and_then(|c| c.as_array_mut()) else {
+        return;
+    };
+
+    for child in content.iter_mut() {
+        match child["type"].as_str() {
+            Some("mediaSingle" | "mediaGroup") => {
+                let placeholders: Vec<String> = child["content"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|m| media_attachment(m, rendered_ids, media))
+                    .collect();
+
+                *child = if placeholders.is_empty() {
+                    json!({ "type": "paragraph", "content": [] })
+                } else {
+                    json!({
+                        "type": "paragraph",
+                        "content": [{ "type": "text", "text": placeholders.join(" ") }]
+                    })
+                };
+            },
+            Some("mediaInline") => {
+                let placeholder = media_attachment(child, rendered_ids, media);
+                *child = json!({ "type": "text", "text": placeholder });
+            },
+            _ => replace_media(child, rendered_ids, media),
+        }
+    }
+}
+
+fn media_attachment(media_node: &Value, rendered_ids: &mut VecDeque<String>, media: &mut Vec<Option<String>>) -> String {
+    let attachment = match media_node["attrs"]["type"].as_str() {
+        Some("external") => {
+            let id = media_node["attrs"]["url"].as_str().and_then(attachment_id).map(|id| id.to_string());
+
+            // Jira renders external images of its own attachments with the same URLs
+            if id.is_some() && rendered_ids.front() == id.as_ref() {
+                rendered_ids.pop_front();
+            }
+
+            id
+        },
+        _ => rendered_ids.pop_front(),
+    };
+
+    media.push(attachment);
+    media_placeholder(media.len() - 1)
+}
@@ -0,0 +1,82 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::http_client::model::ResilientRequest;
+
+use super::model::JiraAPI;
+
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct JiraTransition {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    pub(crate) to: TransitionStatus,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct TransitionStatus {
+    pub(crate) name: String,
+}
+
+impl JiraTransition {
+    /// Transitions available for the issue in its current status.
+    pub(crate) async fn list(jira_api: &JiraAPI, issue_id: &str) -> Result<Vec<Self>> {
+        #[derive(Deserialize)]
+        struct TransitionsResponse {
+            transitions: Vec<JiraTransition>,
+        }
+
+        let response = jira_api.client
+            .get(format!("{}/rest/api/2/issue/{}/transitions", jira_api.config.base_url, issue_id))
+            .basic_auth(&jira_api.config.user, Some(&jira_api.config.token))
+            .send_resilient()
+            .await
+            .context("Failed to send get transitions request")?
+            .error_for_status()
+            .context("Get transitions request bad status")?
+            .json::<TransitionsResponse>()
+            .await
+            .context("Parse get transitions response")?;
+
+        Ok(response.transitions)
+    }
+
+    /// Moves the issue to the status by the first transition leading to it (or named after it).
+    /// Returns `false` if the workflow has no such transition from the current status.
+    pub(crate) async fn to_status(jira_api: &JiraAPI, issue_id: &str, status: &str) -> Result<bool> {
+        let transitions = JiraTransition::list(jira_api, issue_id).await?;
+
+        let Some(transition) = transitions
+            .iter()
+            .find(|t| t.to.name.eq_ignore_ascii_case(status))
+            .or_else(|| transitions.iter().find(|t| t.name.eq_ignore_ascii_case(status)))
+        else {
+            return Ok(false);
+        };
+
+        transition.apply(jira_api, issue_id).await?;
+
+        Ok(true)
+    }
+
+    pub(crate) async fn apply(&self, jira_api: &JiraAPI, issue_id: &str) -> Result<()> {
+        let payload = json!({
+            "transition": {
+                "id": self.id
+            }
+        });
+
+        jira_api.client
+            .post(format!("{}/rest/api/2/issue/{}/transitions", jira_api.config.base_url, issue_id))
+            .basic_auth(&jira_api.config.user, Some(&jira_api.config.token))
+            .json(&payload)
+            .send_resilient()
+            .await
+            .context("Failed to send transition request")?
+            .error_for_status()
+            .context("Transition request bad status")?;
+
+        Ok(())
+    }
+}
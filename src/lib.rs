@@ -1,5 +1,15 @@
 pub mod cfg;
+pub mod http_client;
+pub mod i18n;
 pub mod jira_api;
+pub mod logging;
+pub mod metrics;
 pub mod ms_graph_api;
+pub mod notifier;
+pub mod outbox;
+pub mod reconciler;
+pub mod routes;
+pub mod scheduler;
 pub mod server;
+pub mod store;
 pub mod utils;
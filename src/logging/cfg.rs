@@ -0,0 +1,71 @@
+use std::str::FromStr;
+
+use envconfig::Envconfig;
+use tracing_appender::rolling::Rotation;
+
+#[derive(Envconfig, Clone)]
+pub struct Config {
+    /// `tracing` filter directives like `info,sync_msteams_jira_comments=debug,http=warn`.
+    #[envconfig(from = "LOG_FILTER", default = "info")]
+    pub(crate) filter: String,
+    #[envconfig(from = "LOG_FORMAT", default = "json")]
+    pub(crate) format: LogFormat,
+    /// Folder of rotated log files, logs are written to stdout if empty.
+    #[envconfig(from = "LOG_DIR", default = "")]
+    pub(crate) dir: String,
+    #[envconfig(from = "LOG_ROTATION", default = "daily")]
+    pub(crate) rotation: LogRotation,
+    /// Number of rotated files to keep, 0 to keep all.
+    #[envconfig(from = "LOG_MAX_FILES", default = "7")]
+    pub(crate) max_files: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum LogFormat {
+    /// One JSON object per line with the fields of the current spans.
+    Json,
+    /// Human readable lines.
+    Text,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum LogRotation {
+    Hourly,
+    Daily,
+    Never,
+}
+
+impl LogRotation {
+    pub(crate) fn to_rotation(self) -> Rotation {
+        match self {
+            Self::Hourly => Rotation::HOURLY,
+            Self::Daily => Rotation::DAILY,
+            Self::Never => Rotation::NEVER,
+        }
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "text" => Ok(Self::Text),
+            _ => anyhow::bail!("Unknown log format: {s}"),
+        }
+    }
+}
+
+impl FromStr for LogRotation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "hourly" => Ok(Self::Hourly),
+            "daily" => Ok(Self::Daily),
+            "never" => Ok(Self::Never),
+            _ => anyhow::bail!("Unknown log rotation: {s}"),
+        }
+    }
+}
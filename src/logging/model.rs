@@ -0,0 +1,103 @@
+use anyhow::{Context, Result};
+use axum::{
+    extract::Request,
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+use tokio::time::Instant;
+use tracing::{info, info_span, Instrument};
+use tracing_appender::{non_blocking::WorkerGuard, rolling::RollingFileAppender};
+use tracing_subscriber::EnvFilter;
+use uuid::Uuid;
+
+use super::cfg::{Config, LogFormat};
+
+/// Header with the correlation ID, taken from the request if present and returned in the response.
+pub(crate) const CORRELATION_ID_HEADER: &str = "x-correlation-id";
+const LOG_FILE_PREFIX: &str = "sync_msteams_jira_comments";
+const MAX_CORRELATION_ID_LEN: usize = 128;
+
+/// ID of the incoming webhook, kept with the queued event so its processing is logged under the same ID.
+#[derive(Clone, Debug)]
+pub(crate) struct CorrelationId(pub(crate) String);
+
+impl CorrelationId {
+    pub(crate) fn new() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+}
+
+/// Installs the global subscriber. Lines are written by a background thread,
+/// so the guard must be kept until the process stops, otherwise buffered lines are lost.
+pub fn init(config: &Config) -> Result<WorkerGuard> {
+    let filter = EnvFilter::try_new(&config.filter).context("Invalid LOG_FILTER")?;
+
+    let (writer, guard) = if config.dir.is_empty() {
+        tracing_appender::non_blocking(std::io::stdout())
+    } else {
+        let mut builder = RollingFileAppender::builder()
+            .rotation(config.rotation.to_rotation())
+            .filename_prefix(LOG_FILE_PREFIX)
+            .filename_suffix("log");
+
+        if config.max_files > 0 {
+            builder = builder.max_log_files(config.max_files);
+        }
+
+        let appender = builder
+            .build(&config.dir)
+            .with_context(|| format!("Failed to open log folder {}", config.dir))?;
+
+        tracing_appender::non_blocking(appender)
+    };
+
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .with_ansi(config.dir.is_empty() && config.format == LogFormat::Text);
+
+    match config.format {
+        LogFormat::Json => subscriber.json().with_current_span(false).with_span_list(true).try_init(),
+        LogFormat::Text => subscriber.try_init(),
+    }
+    .map_err(|e| anyhow::anyhow!(e))
+    .context("Failed to init logging")?;
+
+    Ok(guard)
+}
+
+/// Logs every incoming request within a span carrying its correlation ID.
+pub(crate) async fn correlate(mut request: Request, next: Next) -> Response {
+    let correlation_id = request
+        .headers()
+        .get(CORRELATION_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty() && v.len() <= MAX_CORRELATION_ID_LEN)
+        .map(|v| CorrelationId(v.to_string()))
+        .unwrap_or_else(CorrelationId::new);
+
+    let span = info_span!(
+        "request",
+        correlation_id = %correlation_id.0,
+        method = %request.method(),
+        path = request.uri().path(),
+    );
+
+    request.extensions_mut().insert(correlation_id.clone());
+
+    async move {
+        let started = Instant::now();
+        let mut response = next.run(request).await;
+
+        info!(status = response.status().as_u16(), latency_ms = started.elapsed().as_millis() as u64, "Request handled");
+
+        if let Ok(value) = HeaderValue::from_str(&correlation_id.0) {
+            response.headers_mut().insert(CORRELATION_ID_HEADER, value);
+        }
+
+        response
+    }
+    .instrument(span)
+    .await
+}
@@ -0,0 +1,135 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram, Unit};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use tokio::time::{Duration, Instant};
+
+use crate::server::AppStateShared;
+
+const WEBHOOK_EVENTS: &str = "webhook_events_total";
+const SYNCED_ITEMS: &str = "synced_items_total";
+const HTTP_REQUESTS: &str = "http_client_request_duration_seconds";
+const HTTP_THROTTLED: &str = "http_client_throttled_total";
+const HTTP_RETRIES: &str = "http_client_retries_total";
+const JIRA_USER_LOOKUP: &str = "jira_user_lookup_duration_seconds";
+const JIRA_USER_LOOKUP_PAGES: &str = "jira_user_lookup_pages";
+const OUTBOX_EVENTS: &str = "outbox_events";
+const TOKEN_EXPIRES_IN: &str = "token_expires_in_seconds";
+const SUBSCRIPTION_EXPIRES_IN: &str = "subscription_expires_in_seconds";
+
+const LATENCY_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+const PAGES_BUCKETS: &[f64] = &[1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0];
+
+/// Prometheus exporter. Values are recorded by `metrics` macros anywhere in the crate,
+/// gauges of tokens, subscriptions and the outbox are read when metrics are scraped.
+pub struct Metrics {
+    handle: PrometheusHandle,
+}
+
+impl Metrics {
+    /// Installs the global recorder, so it must be created once.
+    pub fn new() -> Result<Self> {
+        let handle = PrometheusBuilder::new()
+            .set_buckets(LATENCY_BUCKETS)?
+            .set_buckets_for_metric(metrics_exporter_prometheus::Matcher::Full(JIRA_USER_LOOKUP_PAGES.to_string()), PAGES_BUCKETS)?
+            .install_recorder()
+            .context("Failed to install metrics recorder")?;
+
+        describe_counter!(WEBHOOK_EVENTS, "Processed webhooks by source, type and outcome");
+        describe_counter!(SYNCED_ITEMS, "Issues and comments synced to Jira, replies synced to Teams");
+        describe_histogram!(HTTP_REQUESTS, Unit::Seconds, "Latency of Jira and Graph API requests by endpoint");
+        describe_counter!(HTTP_THROTTLED, "Responses with 429 Too Many Requests");
+        describe_counter!(HTTP_RETRIES, "Requests retried after an error");
+        describe_histogram!(JIRA_USER_LOOKUP, Unit::Seconds, "Duration of Jira user lookup by email");
+        describe_histogram!(JIRA_USER_LOOKUP_PAGES, "Pages of users read by Jira user lookup by email");
+        describe_gauge!(OUTBOX_EVENTS, "Queued webhooks by status");
+        describe_gauge!(TOKEN_EXPIRES_IN, Unit::Seconds, "Time until the access token expires, 0 if there is no token");
+        describe_gauge!(SUBSCRIPTION_EXPIRES_IN, Unit::Seconds, "Time until the Graph subscription expires, 0 if it isn't created");
+
+        Ok(Self { handle })
+    }
+
+    /// Metrics in Prometheus text format.
+    pub(crate) async fn render(&self, state_shared: &AppStateShared) -> Result<String> {
+        for (status, count) in state_shared.store.count_events().await? {
+            gauge!(OUTBOX_EVENTS, "status" => status.as_str()).set(count as f64);
+        }
+
+        let microsoft = &state_shared.microsoft;
+        let status = microsoft.state_status().await;
+
+        let application = status.token_expires_at.map(|t| t.saturating_duration_since(std::time::Instant::now()));
+
+        gauge!(TOKEN_EXPIRES_IN, "token" => "application").set(seconds(application));
+
+        for subscription in status.subscriptions {
+            let expires_in = subscription.expires_at.and_then(|e| (e - Utc::now()).to_std().ok());
+
+            gauge!(SUBSCRIPTION_EXPIRES_IN, "project_key" => subscription.project_key).set(seconds(expires_in));
+        }
+
+        gauge!(TOKEN_EXPIRES_IN, "token" => "delegated").set(seconds(microsoft.granted_token.read().await.expires_in()));
+
+        self.handle.run_upkeep();
+
+        Ok(self.handle.render())
+    }
+}
+
+fn seconds(duration: Option<Duration>) -> f64 {
+    duration.map(|d| d.as_secs_f64()).unwrap_or_default()
+}
+
+/// Outcome of the queued webhook: `processed`, `retry` or `dead`.
+pub(crate) fn record_webhook(source: &'static str, kind: &str, outcome: &'static str) {
+    counter!(WEBHOOK_EVENTS, "source" => source, "kind" => kind.to_string(), "outcome" => outcome).increment(1);
+}
+
+/// Item is `issue`, `comment` or `reply`, action is `created` or `updated`.
+pub(crate) fn record_synced(item: &'static str, created: bool) {
+    let action = if created { "created" } else { "updated" };
+
+    counter!(SYNCED_ITEMS, "item" => item, "action" => action).increment(1);
+}
+
+/// Status is `error` when no response was received.
+pub(crate) fn record_http_request(host: &str, method: &str, path: &str, status: Option<u16>, started: Instant) {
+    let status = status.map(|s| s.to_string()).unwrap_or_else(|| "error".to_string());
+
+    if status == "429" {
+        counter!(HTTP_THROTTLED, "host" => host.to_string()).increment(1);
+    }
+
+    histogram!(
+        HTTP_REQUESTS,
+        "host" => host.to_string(),
+        "method" => method.to_string(),
+        "endpoint" => endpoint(path),
+        "status" => status,
+    )
+    .record(started.elapsed().as_secs_f64());
+}
+
+pub(crate) fn record_retry(host: &str, reason: &'static str) {
+    counter!(HTTP_RETRIES, "host" => host.to_string(), "reason" => reason).increment(1);
+}
+
+pub(crate) fn record_jira_user_lookup(pages: u64, started: Instant) {
+    histogram!(JIRA_USER_LOOKUP).record(started.elapsed().as_secs_f64());
+    histogram!(JIRA_USER_LOOKUP_PAGES).record(pages as f64);
+}
+
+/// Path with IDs, emails and file names replaced by `{id}`, so endpoints don't multiply series.
+fn endpoint(path: &str) -> String {
+    path
+        .split('/')
+        .map(|segment| {
+            let is_name = segment.chars().all(|c| c.is_ascii_alphabetic() || matches!(c, '_' | '-' | '$'));
+            let is_version = segment.strip_prefix('v').is_some_and(|v| !v.is_empty() && v.chars().all(|c| c.is_ascii_digit() || c == '.'))
+                || (segment.len() == 1 && segment.chars().all(|c| c.is_ascii_digit()));
+
+            if is_name || is_version { segment } else { "{id}" }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
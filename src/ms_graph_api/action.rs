@@ -0,0 +1,122 @@
+use anyhow::{bail, ensure, Context, Result};
+use chrono::Utc;
+use hmac::{Hmac, KeyInit, Mac};
+use reqwest::Url;
+use serde::Deserialize;
+use sha2::Sha256;
+
+use super::cfg::Config;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Action of a card button.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum IssueAction {
+    /// The fix is confirmed, the issue is moved to `JIRA_CONFIRMED_STATUS`.
+    Confirm,
+    /// The fix didn't help, the issue is moved to `JIRA_REJECTED_STATUS`.
+    Reject,
+}
+
+/// Card button link, signed with `TEAMS_ACTION_SECRET` and valid until `expires` (unix time).
+#[derive(Debug, Deserialize)]
+pub(crate) struct SignedAction {
+    pub(crate) issue: String,
+    action: String,
+    expires: i64,
+    signature: String,
+}
+
+impl IssueAction {
+    pub(crate) const ALL: [Self; 2] = [Self::Confirm, Self::Reject];
+
+    fn parse(s: &str) -> Result<Self> {
+        match s {
+            "confirm" => Ok(Self::Confirm),
+            "reject" => Ok(Self::Reject),
+            _ => bail!("Unknown action: {s}"),
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Self::Confirm => "confirm",
+            Self::Reject => "reject",
+        }
+    }
+
+    /// Catalog key of the button title.
+    pub(crate) fn title_key(&self) -> &'static str {
+        match self {
+            Self::Confirm => "card_confirm",
+            Self::Reject => "card_reject",
+        }
+    }
+
+    /// Signed URL of the button valid for `TEAMS_ACTION_TTL_HOURS`, `None` if actions aren't configured.
+    pub(crate) fn url(&self, config: &Config, issue_id: &str) -> Option<String> {
+        if config.action_url.is_empty() || config.action_secret.is_empty() {
+            return None;
+        }
+
+        let expires = Utc::now().timestamp() + i64::from(config.action_ttl_hours) * 60 * 60;
+        let signature = sign(&config.action_secret, issue_id, *self, expires).ok()?;
+
+        Url::parse_with_params(&config.action_url, &[
+                ("issue", issue_id),
+                ("action", self.as_str()),
+                ("expires", &expires.to_string()),
+                ("signature", &signature),
+            ])
+            .ok()
+            .map(String::from)
+    }
+}
+
+impl SignedAction {
+    /// Action of the link if the signature is valid. Expiration is checked separately.
+    pub(crate) fn verify(&self, secret: &str) -> Result<IssueAction> {
+        ensure!(!secret.is_empty(), "Actions are disabled");
+
+        let action = IssueAction::parse(&self.action)?;
+
+        let mut mac = HmacSha256::new_from_slice(secret.as_bytes())?;
+        mac.update(signed_data(&self.issue, action, self.expires).as_bytes());
+        mac.verify_slice(&hex::decode(&self.signature)?).context("Wrong signature")?;
+
+        Ok(action)
+    }
+
+    pub(crate) fn is_expired(&self) -> bool {
+        self.expires <= Utc::now().timestamp()
+    }
+
+    /// The link passed through the sign-in as OAuth `state`.
+    pub(crate) fn to_state(&self) -> String {
+        format!("{}.{}.{}.{}", self.issue, self.action, self.expires, self.signature)
+    }
+
+    pub(crate) fn from_state(state: &str) -> Result<Self> {
+        let [issue, action, expires, signature] = state.split('.').collect::<Vec<_>>()[..] else {
+            bail!("Malformed state");
+        };
+
+        Ok(Self {
+            issue: issue.to_string(),
+            action: action.to_string(),
+            expires: expires.parse().context("Malformed expiration time")?,
+            signature: signature.to_string(),
+        })
+    }
+}
+
+fn sign(secret: &str, issue_id: &str, action: IssueAction, expires: i64) -> Result<String> {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())?;
+    mac.update(signed_data(issue_id, action, expires).as_bytes());
+
+    Ok(hex::encode(mac.finalize().into_bytes()))
+}
+
+fn signed_data(issue_id: &str, action: IssueAction, expires: i64) -> String {
+    format!("{}:{}:{}", issue_id, action.as_str(), expires)
+}
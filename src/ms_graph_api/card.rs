@@ -0,0 +1,115 @@
+use regex::Regex;
+use serde_json::{json, Value};
+
+use crate::i18n::model::Catalog;
+
+use super::message::TeamsAttachment;
+
+/// ID of the card attachment, referenced by `<attachment id="...">` tag in the reply body.
+const CARD_ATTACHMENT_ID: &str = "issue-card";
+
+/// Issue shown as Adaptive Card in the thread.
+pub(crate) struct IssueCard<'a> {
+    pub(crate) key: &'a str,
+    pub(crate) url: &'a str,
+    pub(crate) summary: &'a str,
+    pub(crate) status: &'a str,
+    /// Final statuses get a green badge.
+    pub(crate) is_final: bool,
+    pub(crate) assignee: Option<&'a str>,
+    pub(crate) priority: Option<&'a str>,
+    /// Notification template filled for the last change, HTML.
+    pub(crate) notice: Option<&'a str>,
+    /// Buttons besides "Open in Jira".
+    pub(crate) actions: Vec<CardAction>,
+}
+
+/// Button opening the URL.
+pub(crate) struct CardAction {
+    pub(crate) title: String,
+    pub(crate) url: String,
+}
+
+impl IssueCard<'_> {
+    /// Reply body and the card attachment it references.
+    pub(crate) fn to_reply(&self, catalog: &Catalog, locale: Option<&str>) -> (String, TeamsAttachment) {
+        let attachment = TeamsAttachment {
+            id: Some(CARD_ATTACHMENT_ID.to_string()),
+            content_type: Some("application/vnd.microsoft.card.adaptive".to_string()),
+            content_url: None,
+            name: None,
+            content: Some(self.to_json(catalog, locale).to_string()),
+        };
+
+        (format!("<attachment id=\"{}\"></attachment>", CARD_ATTACHMENT_ID), attachment)
+    }
+
+    fn to_json(&self, catalog: &Catalog, locale: Option<&str>) -> Value {
+        let mut body = vec![
+            json!({
+                "type": "ColumnSet",
+                "columns": [
+                    {
+                        "type": "Column",
+                        "width": "stretch",
+                        "verticalContentAlignment": "Center",
+                        "items": [{ "type": "TextBlock", "text": self.key, "weight": "Bolder", "size": "Medium" }]
+                    },
+                    {
+                        "type": "Column",
+                        "width": "auto",
+                        "items": [{
+                            "type": "Container",
+                            "style": if self.is_final { "good" } else { "accent" },
+                            "items": [{ "type": "TextBlock", "text": self.status, "weight": "Bolder", "size": "Small" }]
+                        }]
+                    }
+                ]
+            }),
+            json!({ "type": "TextBlock", "text": self.summary, "wrap": true }),
+            json!({
+                "type": "FactSet",
+                "facts": [
+                    { "title": catalog.get(locale, "card_status"), "value": self.status },
+                    {
+                        "title": catalog.get(locale, "card_assignee"),
+                        "value": self.assignee.unwrap_or_else(|| catalog.get(locale, "card_unassigned"))
+                    },
+                    { "title": catalog.get(locale, "card_priority"), "value": self.priority.unwrap_or("-") }
+                ]
+            }),
+        ];
+
+        if let Some(notice) = self.notice.map(plain_text).filter(|n| !n.is_empty()) {
+            body.push(json!({ "type": "TextBlock", "text": notice, "wrap": true, "isSubtle": true, "separator": true }));
+        }
+
+        let mut actions: Vec<Value> = self.actions
+            .iter()
+            .map(|a| json!({ "type": "Action.OpenUrl", "title": a.title, "url": a.url }))
+            .collect();
+
+        actions.push(json!({ "type": "Action.OpenUrl", "title": catalog.get(locale, "card_open_in_jira"), "url": self.url }));
+
+        json!({
+            "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+            "type": "AdaptiveCard",
+            "version": "1.4",
+            "body": body,
+            "actions": actions
+        })
+    }
+}
+
+/// Notification templates are HTML, cards take plain text with line breaks.
+fn plain_text(html: &str) -> String {
+    let text = Regex::new(r"(?i)<br\s*/?>").unwrap().replace_all(html, "\n\n");
+    let text = Regex::new(r"<[^>]*>").unwrap().replace_all(&text, "");
+
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
+}
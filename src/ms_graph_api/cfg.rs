@@ -1,3 +1,7 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::ensure;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
 use envconfig::Envconfig;
 
 #[derive(Envconfig, Clone)]
@@ -18,6 +22,56 @@ pub struct Config {
     pub(crate) group_id: String,
     #[envconfig(from = "TEAMS_CHANNEL_ID", default = "")]
     pub(crate) channel_id: String,
+    /// Base64 encoded 256-bit key encrypting the refresh token of `TEAMS_USER` in the store.
+    /// The token isn't kept across restarts if empty.
+    #[envconfig(from = "MICROSOFT_TOKEN_KEY", default = "")]
+    pub(crate) token_key: TokenKey,
     #[envconfig(from = "TEAMS_USER", default = "")]
     pub(crate) teams_user: String,
+    /// Shows the issue as Adaptive Card updated in place instead of posting notices.
+    #[envconfig(from = "TEAMS_CARDS_ENABLED", default = "false")]
+    pub(crate) cards_enabled: bool,
+    /// Public URL of `/teams_action` endpoint, card buttons acting on issues are shown if set.
+    #[envconfig(from = "TEAMS_ACTION_URL", default = "")]
+    pub(crate) action_url: String,
+    /// Key signing URLs of card buttons.
+    #[envconfig(from = "TEAMS_ACTION_SECRET", default = "")]
+    pub(crate) action_secret: String,
+    /// Card buttons stop working this many hours after the card was posted or updated.
+    #[envconfig(from = "TEAMS_ACTION_TTL_HOURS", default = "168")]
+    pub(crate) action_ttl_hours: u32,
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct TokenKey(Option<[u8; 32]>);
+
+impl TokenKey {
+    pub(crate) fn get(&self) -> Option<&[u8; 32]> {
+        self.0.as_ref()
+    }
+}
+
+impl FromStr for TokenKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Ok(Self::default());
+        }
+
+        let key = STANDARD.decode(s.trim())?;
+        ensure!(key.len() == 32, "Key must be 32 bytes long");
+
+        let mut result = [0; 32];
+        result.copy_from_slice(&key);
+
+        Ok(Self(Some(result)))
+    }
+}
+
+/// Keeps the key out of logs.
+impl fmt::Debug for TokenKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TokenKey(..)")
+    }
 }
@@ -1,10 +1,21 @@
-use anyhow::{ensure, Context, Result};
+use aes_gcm::{
+    aead::{Aead, Generate, KeyInit},
+    Aes256Gcm, Nonce,
+};
+use anyhow::{anyhow, ensure, Context, Result};
 use reqwest::Client;
 use serde::Deserialize;
+use std::fmt;
 use tokio::time::{Duration, Instant};
 
+use crate::{http_client::model::ResilientRequest, store::model::Store};
+
 use super::cfg::Config;
 
+/// Name of the encrypted refresh token in the store.
+const REFRESH_TOKEN_SECRET: &str = "delegated_refresh_token";
+const NONCE_LEN: usize = 12;
+
 #[derive(Deserialize)]
 pub(crate) struct GrantedToken {
     access_token: String,
@@ -40,14 +51,22 @@ impl GrantedToken {
 
     async fn set(&mut self, client: &Client, config: &Config, form: &[(&str, &str)]) -> Result<(String, u64)> {
 
-        let token = client
+        let response = client
             .post(format!("https://login.microsoftonline.com/{}/oauth2/v2.0/token", config.tenant_id))
             .form(form)
-            .send()
+            .send_resilient()
             .await
-            .context("Failed to send get token request")?
-            .error_for_status()
-            .context("Get token request bad status")?
+            .context("Failed to send get token request")?;
+
+        let status = response.status();
+
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            let error = serde_json::from_str::<TokenError>(&body).unwrap_or_default();
+            return Err(anyhow::Error::new(error)).context(format!("Get token request bad status: {status}"));
+        }
+
+        let token = response
             .json::<GrantedToken>()
             .await
             .context("Parse get token response")?;
@@ -66,6 +85,53 @@ impl GrantedToken {
         Ok(self.access_token.clone())
     }
 
+    /// Time until the access token expires, `None` if there is no token.
+    pub(crate) fn expires_in(&self) -> Option<Duration> {
+        Some(self.expires_at.saturating_duration_since(Instant::now())).filter(|_| !self.access_token.is_empty())
+    }
+
+    pub(crate) fn has_refresh_token(&self) -> bool {
+        !self.refresh_token.is_empty()
+    }
+
+    /// Forgets the token, so the Teams user has to authenticate again.
+    pub(crate) fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Restores the refresh token saved by the previous run. Returns `false` if there is nothing to restore.
+    pub(crate) async fn load(&mut self, store: &Store, config: &Config) -> Result<bool> {
+        let Some(key) = config.token_key.get() else {
+            return Ok(false);
+        };
+
+        let Some(encrypted) = store.get_secret(REFRESH_TOKEN_SECRET).await? else {
+            return Ok(false);
+        };
+
+        let refresh_token = decrypt(key, &encrypted).context("Failed to decrypt refresh token")?;
+
+        self.clear();
+        self.refresh_token = refresh_token;
+
+        Ok(true)
+    }
+
+    /// Saves the refresh token encrypted with `MICROSOFT_TOKEN_KEY`, if the key is set.
+    pub(crate) async fn save(&self, store: &Store, config: &Config) -> Result<()> {
+        let Some(key) = config.token_key.get() else {
+            return Ok(());
+        };
+
+        let encrypted = encrypt(key, &self.refresh_token)?;
+
+        store.set_secret(REFRESH_TOKEN_SECRET, &encrypted).await
+    }
+
+    pub(crate) async fn remove(store: &Store) -> Result<()> {
+        store.remove_secret(REFRESH_TOKEN_SECRET).await
+    }
+
     pub(crate) async fn refresh_and_get_expiration_time(&mut self, client: &Client, config: &Config) -> Result<u64> {
         let refresh_token = self.refresh_token.clone();
         let form = [
@@ -81,3 +147,54 @@ impl GrantedToken {
         Ok(expires_in)
     }
 }
+
+/// OAuth error response of the token endpoint.
+#[derive(Debug, Default, Deserialize)]
+struct TokenError {
+    #[serde(default)]
+    error: String,
+    #[serde(default)]
+    error_description: String,
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.error, self.error_description)
+    }
+}
+
+impl std::error::Error for TokenError {}
+
+/// Whether the token endpoint rejected the refresh token, e.g. it was revoked or expired.
+/// Other errors are transient and the refresh is retried.
+pub(crate) fn is_invalid_grant(err: &anyhow::Error) -> bool {
+    err
+        .downcast_ref::<TokenError>()
+        .is_some_and(|e| e.error == "invalid_grant")
+}
+
+/// Returns nonce followed by the ciphertext.
+fn encrypt(key: &[u8; 32], value: &str) -> Result<Vec<u8>> {
+    let cipher = Aes256Gcm::new(key.into());
+    let nonce = Nonce::generate();
+
+    let ciphertext = cipher
+        .encrypt(&nonce, value.as_bytes())
+        .map_err(|_| anyhow!("Failed to encrypt"))?;
+
+    Ok([nonce.as_slice(), &ciphertext].concat())
+}
+
+fn decrypt(key: &[u8; 32], data: &[u8]) -> Result<String> {
+    ensure!(data.len() > NONCE_LEN, "Encrypted value is too short");
+
+    let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+    let nonce = Nonce::try_from(nonce).map_err(|_| anyhow!("Wrong nonce length"))?;
+    let cipher = Aes256Gcm::new(key.into());
+
+    let value = cipher
+        .decrypt(&nonce, ciphertext)
+        .map_err(|_| anyhow!("Wrong key or corrupted value"))?;
+
+    Ok(String::from_utf8(value)?)
+}
@@ -0,0 +1,151 @@
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use reqwest::Url;
+use serde::Deserialize;
+
+use crate::{http_client::model::ResilientRequest, routes::model::Route, utils::get_reqwest_client};
+
+use super::message::TeamsAttachment;
+
+/// File shared in Teams, stored in SharePoint or OneDrive.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DriveItem {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) size: u64,
+    pub(crate) file: Option<FileFacet>,
+    pub(crate) web_url: Option<String>,
+    /// Like `"{5A2E1C4B-...},3"`, the GUID is ID of Teams attachment.
+    pub(crate) e_tag: Option<String>,
+    pub(crate) parent_reference: Option<ItemReference>,
+    #[serde(skip)]
+    share_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ItemReference {
+    pub(crate) drive_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FileFacet {
+    pub(crate) mime_type: Option<String>,
+}
+
+impl DriveItem {
+    /// Finds drive item by its sharing URL (`contentUrl` of Teams attachment).
+    pub(crate) async fn get(access_token: &str, url: &str) -> Result<Self> {
+        let client = get_reqwest_client()?;
+        let share_id = format!("u!{}", URL_SAFE_NO_PAD.encode(url));
+
+        let mut item = client
+            .get(format!("https://graph.microsoft.com/v1.0/shares/{}/driveItem", share_id))
+            .bearer_auth(access_token)
+            .query(&[("$select", "id,name,size,file,webUrl,eTag")])
+            .send_resilient()
+            .await
+            .context("Failed to send get drive item request")?
+            .error_for_status()
+            .context("Get drive item request bad status")?
+            .json::<Self>()
+            .await
+            .context("Parse get drive item response")?;
+
+        item.share_id = share_id;
+
+        Ok(item)
+    }
+
+    /// Uploads file to the channel's SharePoint folder, replacing the file with the same path.
+    pub(crate) async fn upload_to_channel(access_token: &str, route: &Route, path: &[&str], data: Vec<u8>) -> Result<Self> {
+        let client = get_reqwest_client()?;
+
+        let folder = client
+            .get(format!("https://graph.microsoft.com/v1.0/teams/{}/channels/{}/filesFolder", route.group_id, route.channel_id))
+            .bearer_auth(access_token)
+            .send_resilient()
+            .await
+            .context("Failed to send get files folder request")?
+            .error_for_status()
+            .context("Get files folder request bad status")?
+            .json::<Self>()
+            .await
+            .context("Parse get files folder response")?;
+
+        let drive_id = folder.parent_reference
+            .map(|r| r.drive_id)
+            .ok_or(anyhow!("No drive of the files folder"))?;
+
+        // `items/{folder-id}:/{path}:/content`, missing folders of the path are created
+        let mut url = Url::parse("https://graph.microsoft.com/v1.0/drives")?;
+        let (file_name, folders) = path.split_last().ok_or(anyhow!("Empty file path"))?;
+
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("Invalid base URL"))?
+            .push(&drive_id)
+            .push("items")
+            .push(&format!("{}:", folder.id))
+            .extend(folders)
+            .push(&format!("{}:", file_name))
+            .push("content");
+
+        client
+            .put(url)
+            .bearer_auth(access_token)
+            .body(data)
+            .send_resilient()
+            .await
+            .context("Failed to send upload file request")?
+            .error_for_status()
+            .context("Upload file request bad status")?
+            .json::<Self>()
+            .await
+            .context("Parse upload file response")
+    }
+
+    /// Teams attachment referencing the file.
+    pub(crate) fn to_attachment(&self) -> Option<TeamsAttachment> {
+        let id = self.e_tag
+            .as_deref()?
+            .split(['{', '}'])
+            .nth(1)?
+            .to_lowercase();
+
+        Some(TeamsAttachment {
+            id: Some(id),
+            content_type: Some("reference".to_string()),
+            content_url: self.web_url.clone(),
+            name: Some(self.name.clone()),
+            content: None,
+        })
+    }
+
+    pub(crate) fn mime_type(&self) -> &str {
+        self.file
+            .as_ref()
+            .and_then(|f| f.mime_type.as_deref())
+            .unwrap_or("application/octet-stream")
+    }
+
+    pub(crate) async fn download(&self, access_token: &str) -> Result<Vec<u8>> {
+        let client = get_reqwest_client()?;
+
+        let data = client
+            .get(format!("https://graph.microsoft.com/v1.0/shares/{}/driveItem/content", self.share_id))
+            .bearer_auth(access_token)
+            .send_resilient()
+            .await
+            .context("Failed to send download drive item request")?
+            .error_for_status()
+            .context("Download drive item request bad status")?
+            .bytes()
+            .await
+            .context("Failed to read drive item content")?;
+
+        Ok(data.to_vec())
+    }
+}
@@ -4,10 +4,11 @@ use regex::Regex;
 use reqwest::header::HeaderMap;
 use uuid::Uuid;
 
-use crate::utils::get_reqwest_client;
+use crate::{http_client::model::ResilientRequest, utils::get_reqwest_client};
 
 #[derive(Debug)]
 pub(crate) struct GraphApiImage {
+    pub(crate) url: String,
     pub(crate) name: String,
     pub(crate) data: Vec<u8>,
     pub(crate) mime_str: String,
@@ -20,7 +21,7 @@ impl GraphApiImage {
         let response = client
             .get(url)
             .bearer_auth(access_token)
-            .send()
+            .send_resilient()
             .await
             .context("Failed to send search issue request")?
             .error_for_status()
@@ -29,6 +30,7 @@ impl GraphApiImage {
         let headers = response.headers().clone();
         
         let img = Self {
+            url: url.to_string(),
             name: format!("{}.{}", get_teams_attachment_id(url), get_image_extension(&headers)),
             data: response.bytes().await?.to_vec(),
             mime_str: headers.get("Content-Type").map_or(String::new(), |h| h.to_str().unwrap_or_default().to_string()),
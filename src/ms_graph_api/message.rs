@@ -1,8 +1,11 @@
 use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
 use reqwest::Client;
-use serde::Deserialize;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::{http_client::model::ResilientRequest, routes::model::Route};
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct MsGraphMessage {
@@ -12,6 +15,20 @@ pub(crate) struct MsGraphMessage {
     pub(crate) body: MessageBody,
     pub(crate) attachments: Vec<TeamsAttachment>,
     pub(crate) subject: Option<String>,
+    pub(crate) deleted_date_time: Option<String>,
+    #[serde(default)]
+    pub(crate) mentions: Vec<TeamsMention>,
+    pub(crate) last_modified_date_time: Option<DateTime<Utc>>,
+    /// `message` for messages of users, others are system events.
+    pub(crate) message_type: Option<String>,
+}
+
+/// Page of Graph API collection.
+#[derive(Deserialize)]
+struct Page<T> {
+    value: Vec<T>,
+    #[serde(rename = "@odata.nextLink")]
+    next_link: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -26,12 +43,66 @@ pub(crate) struct MessageBody {
     pub(crate) content: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct TeamsAttachment {
-    // pub(crate) id: Uuid,
+    /// Referenced by `<attachment id="...">` tag in the message body.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) id: Option<String>,
+    /// `reference` for files shared from SharePoint/OneDrive.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) content_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) content_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) name: Option<String>,
+    /// JSON of Adaptive Card and other inline attachments.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) content: Option<String>,
+}
+
+/// Image posted along with the message, referenced as `../hostedContents/<temporary ID>/$value`.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HostedContent {
+    #[serde(rename = "@microsoft.graph.temporaryId")]
+    pub(crate) temporary_id: String,
+    /// Base64 encoded content.
+    pub(crate) content_bytes: String,
+    pub(crate) content_type: String,
+}
+
+/// Entities referenced from HTML body of a reply.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ReplyParts {
+    pub(crate) mentions: Vec<TeamsMention>,
+    pub(crate) hosted_contents: Vec<HostedContent>,
+    pub(crate) attachments: Vec<TeamsAttachment>,
+}
+
+/// Target of `<at id="...">` tag in the message body.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TeamsMention {
+    pub(crate) id: u32,
+    pub(crate) mention_text: String,
+    pub(crate) mentioned: MentionedIdentity,
+}
+
+/// Mentioned user, other identities (channels, teams, apps) are skipped.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub(crate) struct MentionedIdentity {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) user: Option<MentionedUser>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MentionedUser {
+    pub(crate) id: String,
+    pub(crate) display_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) user_identity_type: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -41,12 +112,23 @@ pub(crate) struct MsGraphUser {
     // pub(crate) display_name: Option<String>,
 }
 
+impl ReplyParts {
+    /// Parts of the existing message, to keep them on edit.
+    pub(crate) fn of(message: &MsGraphMessage) -> Self {
+        Self {
+            mentions: message.mentions.clone(),
+            hosted_contents: Vec::new(),
+            attachments: message.attachments.clone(),
+        }
+    }
+}
+
 impl MsGraphMessage {
     pub(crate) async fn get(client: &Client, resource: &str, access_token: &str) -> Result<Self> {
         let message = client
             .get(format!("https://graph.microsoft.com/v1.0/{resource}"))
             .bearer_auth(access_token)
-            .send()
+            .send_resilient()
             .await
             .context("Failed to send get message request")?
             .error_for_status()
@@ -57,4 +139,52 @@ impl MsGraphMessage {
 
         Ok(message)
     }
+
+    /// Root messages of the channel modified after `since`, by delta query.
+    pub(crate) async fn list_modified(client: &Client, access_token: &str, route: &Route, since: DateTime<Utc>) -> Result<Vec<Self>> {
+        let filter = format!("lastModifiedDateTime gt {}", since.to_rfc3339_opts(SecondsFormat::Secs, true));
+
+        let url = reqwest::Url::parse_with_params(
+            &format!("https://graph.microsoft.com/v1.0{}/delta", route.messages_resource()),
+            &[("$filter", filter.as_str())],
+        )?;
+
+        list_pages(client, access_token, url.as_str()).await.context("Failed to list modified messages")
+    }
+
+    pub(crate) async fn list_replies(client: &Client, access_token: &str, route: &Route, message_id: &str) -> Result<Vec<Self>> {
+        let url = format!("https://graph.microsoft.com/v1.0{}/{}/replies?$top=50", route.messages_resource(), message_id);
+
+        list_pages(client, access_token, &url).await.context("Failed to list replies")
+    }
+
+    /// Sent by a user, not a system event.
+    pub(crate) fn is_user_message(&self) -> bool {
+        self.message_type.as_deref().is_none_or(|t| t == "message") && self.from.user.is_some()
+    }
+}
+
+/// Follows `@odata.nextLink` until the last page, delta queries end with `@odata.deltaLink` instead.
+async fn list_pages<T: DeserializeOwned>(client: &Client, access_token: &str, url: &str) -> Result<Vec<T>> {
+    let mut items = Vec::new();
+    let mut next_link = Some(url.to_string());
+
+    while let Some(url) = next_link {
+        let page = client
+            .get(url)
+            .bearer_auth(access_token)
+            .send_resilient()
+            .await
+            .context("Failed to send list request")?
+            .error_for_status()
+            .context("List request bad status")?
+            .json::<Page<T>>()
+            .await
+            .context("Parse list response")?;
+
+        items.extend(page.value);
+        next_link = page.next_link;
+    }
+
+    Ok(items)
 }
@@ -1,5 +1,8 @@
+pub(crate) mod action;
+pub(crate) mod card;
 pub(crate) mod cfg;
 pub(crate) mod delegated_token;
+pub(crate) mod drive_item;
 pub(crate) mod image;
 pub(crate) mod message;
 pub mod model;
@@ -1,55 +1,36 @@
+use std::time::Instant;
+
 use anyhow::{ensure, Context, Result};
-use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use reqwest::Client;
 use serde::Deserialize;
 use serde_json::json;
 use tokio::{
     sync::{Mutex, RwLock},
     time::{sleep, Duration},
 };
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
-/// Maximum number of retry attempts when the Graph API responds with 429 Too Many Requests.
-const MAX_THROTTLE_RETRIES: u32 = 5;
-/// Fallback delay used when the 429 response does not include a `Retry-After` header.
-const DEFAULT_THROTTLE_RETRY_SECS: u64 = 10;
-/// Upper bound to avoid sleeping for pathologically large `Retry-After` values.
-const MAX_THROTTLE_RETRY_SECS: u64 = 120;
-
-/// Send a request, transparently retrying on HTTP 429 responses.
-///
-/// Honours the `Retry-After` header (in seconds) when present; otherwise falls back
-/// to a fixed delay. Gives up after `MAX_THROTTLE_RETRIES` attempts.
-async fn send_with_throttle_retry(builder: RequestBuilder) -> Result<Response> {
-    let mut attempt: u32 = 0;
-    loop {
-        let request = builder
-            .try_clone()
-            .context("Failed to clone request for retry")?;
-
-        let response = request.send().await.context("Failed to send request")?;
-
-        if response.status() != StatusCode::TOO_MANY_REQUESTS || attempt >= MAX_THROTTLE_RETRIES {
-            return Ok(response);
-        }
-
-        let retry_after = response
-            .headers()
-            .get(reqwest::header::RETRY_AFTER)
-            .and_then(|v| v.to_str().ok())
-            .and_then(|v| v.parse::<u64>().ok())
-            .unwrap_or(DEFAULT_THROTTLE_RETRY_SECS)
-            .min(MAX_THROTTLE_RETRY_SECS);
-
-        sleep(Duration::from_secs(retry_after)).await;
-        attempt += 1;
-    }
-}
+/// How often subscriptions are checked for renewal.
+const SUBSCRIPTIONS_CHECK_INTERVAL_SECS: u64 = 60;
+/// Delay before retrying the delegated token refresh after a transient error.
+const GRANTED_TOKEN_RETRY_SECS: u64 = 10;
+/// Longest delay before sending the authentication link again after a failure.
+const GRANTED_TOKEN_MAX_RETRY_SECS: u64 = 10 * 60;
 
-use crate::utils::get_reqwest_client;
+use crate::{
+    http_client::model::ResilientRequest,
+    i18n::model::Catalog, 
+    jira_api::model::is_not_found,
+    notifier::model::{Alert, Notifier},
+    routes::model::{Route, Routes}, 
+    store::model::Store,
+    utils::{fill_html_template, get_reqwest_client},
+};
 
-use super::{cfg::Config, message::MsGraphMessage};
-use super::delegated_token::GrantedToken;
-use super::subscription::Subscription;
+use super::{cfg::Config, message::{MsGraphMessage, ReplyParts}};
+use super::delegated_token::{is_invalid_grant, GrantedToken};
+use super::subscription::{Subscription, SubscriptionStatus};
 use super::token::ApplicationToken;
 
 pub struct MSGraphAPI {
@@ -60,32 +41,73 @@ pub struct MSGraphAPI {
     pub(crate) users: RwLock<Vec<MsUser>>,
 }
 
+/// Application token and subscriptions of the state, read by probes and metrics.
+#[derive(Clone)]
+pub(crate) struct StateStatus {
+    /// Expiration of the application token, `None` if there is no token.
+    pub(crate) token_expires_at: Option<Instant>,
+    pub(crate) subscriptions: Vec<SubscriptionStatus>,
+}
+
 pub struct MSGraphAPIState {
     pub token: ApplicationToken,
-    pub subscription: Subscription,
+    pub subscriptions: Vec<Subscription>,
+    /// `state` parameter of the authentication link sent to the Teams user.
+    auth_state: Uuid,
 }
 
-#[derive(Deserialize)]
+#[derive(Clone, Debug, Default, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct MsUser {
     pub(crate) id: Uuid,
     pub(crate) mail: String,
+    pub(crate) display_name: Option<String>,
+    /// Locale like `en-US`, used to translate replies to the user.
+    pub(crate) preferred_language: Option<String>,
 }
 
 impl MSGraphAPIState {
-    fn new() -> Self {
+    fn new(routes: &Routes) -> Self {
         Self {
             token: ApplicationToken::new(),
-            subscription: Subscription::new(),
+            subscriptions: routes.iter().cloned().map(Subscription::new).collect(),
+            auth_state: Uuid::nil(),
+        }
+    }
+
+    /// Returns subscription with the given ID if the secret matches.
+    pub(crate) fn check_client_secret(&mut self, subscription_id: &str, secret: &str) -> Result<&mut Subscription> {
+        let subscription = self.subscriptions
+            .iter_mut()
+            .find(|s| s.id().to_string().eq_ignore_ascii_case(subscription_id))
+            .context("Unknown subscription")?;
+
+        subscription.check_client_secret(secret)?;
+
+        Ok(subscription)
+    }
+
+    pub(crate) fn check_auth_state(&self, state: &str) -> Result<()> {
+        let state_uuid = Uuid::try_parse(state)?;
+        ensure!(!self.auth_state.is_nil() && state_uuid == self.auth_state, "Incorrect state");
+        Ok(())
+    }
+
+    fn status(&self) -> StateStatus {
+        StateStatus {
+            token_expires_at: Some(self.token.expires_at).filter(|_| !self.token.value.is_empty()),
+            subscriptions: self.subscriptions.iter().map(|s| s.status()).collect(),
         }
     }
 }
 
 impl MSGraphAPI {
-    pub fn new(config: Config) -> Result<Self> {
+    pub fn new(config: Config, routes: &Routes) -> Result<Self> {
+        let state = MSGraphAPIState::new(routes);
+
         let graph_api = Self { 
             config,
-            state: Mutex::new(MSGraphAPIState::new()),
+            state: Mutex::new(state),
             client: get_reqwest_client()?,
             granted_token: RwLock::new(GrantedToken::new()),
             users: RwLock::new(Vec::new()),
@@ -93,72 +115,428 @@ impl MSGraphAPI {
         Ok(graph_api)
     }
 
-    pub(crate) async fn get_user_email(&self, access_token: &str, user_id: Uuid) -> Result<String> {
-        let user_mail = self
+    /// Returns Teams user, cached after the first request.
+    pub(crate) async fn get_user(&self, access_token: &str, user_id: Uuid) -> Result<MsUser> {
+        let cached = self
             .users
             .read()
             .await
             .iter()
             .find(|u| u.id == user_id)
-            .map(|u| u.mail.clone());
+            .cloned();
         
-        if user_mail.is_none() {
-            let new_user = self.client
-                .get(format!("https://graph.microsoft.com/v1.0/users/{}", user_id))
-                .bearer_auth(access_token)
-                .send()
-                .await
-                .context("Failed to send get user mail request")?
-                .error_for_status()
-                .context("Get user mail request bad status")?
-                .json::<MsUser>()
-                .await
-                .context("Parse get user mail response")?;
-
-            let new_user_mail = new_user.mail.clone();
-            
-            self
-                .users
-                .write()
-                .await
-                .push(new_user);
-
-            Ok(new_user_mail)
-        } else {
-            Ok(user_mail.unwrap_or_default())
+        if let Some(user) = cached {
+            return Ok(user);
+        }
+
+        let new_user = self.client
+            .get(format!("https://graph.microsoft.com/v1.0/users/{}?$select=id,mail,displayName,preferredLanguage", user_id))
+            .bearer_auth(access_token)
+            .send_resilient()
+            .await
+            .context("Failed to send get user request")?
+            .error_for_status()
+            .context("Get user request bad status")?
+            .json::<MsUser>()
+            .await
+            .context("Parse get user response")?;
+
+        self
+            .users
+            .write()
+            .await
+            .push(new_user.clone());
+
+        Ok(new_user)
+    }
+
+    /// Finds Teams user by email, `None` if there is no such user.
+    pub(crate) async fn find_user_by_email(&self, access_token: &str, email: &str) -> Result<Option<MsUser>> {
+        let cached = self
+            .users
+            .read()
+            .await
+            .iter()
+            .find(|u| u.mail.eq_ignore_ascii_case(email))
+            .cloned();
+
+        if cached.is_some() {
+            return Ok(cached);
         }
+
+        #[derive(Deserialize)]
+        struct UsersResponse {
+            value: Vec<MsUser>,
+        }
+
+        let response = self.client
+            .get("https://graph.microsoft.com/v1.0/users")
+            .bearer_auth(access_token)
+            .query(&[
+                ("$filter", format!("mail eq '{}'", email.replace('\'', "''"))),
+                ("$select", "id,mail,displayName,preferredLanguage".to_string()),
+            ])
+            .send_resilient()
+            .await
+            .context("Failed to send find user request")?
+            .error_for_status()
+            .context("Find user request bad status")?
+            .json::<UsersResponse>()
+            .await
+            .context("Parse find user response")?;
+
+        let Some(new_user) = response.value.into_iter().next() else {
+            return Ok(None);
+        };
+
+        self
+            .users
+            .write()
+            .await
+            .push(new_user.clone());
+
+        Ok(Some(new_user))
+    }
+
+    /// Application token, renewed if it's expired. The state isn't held during the renewal.
+    pub(crate) async fn get_token(&self) -> Result<String> {
+        if let Ok(token) = self.state.lock().await.token.get() {
+            return Ok(token);
+        }
+
+        let token = ApplicationToken::request(&self.client, &self.config).await?;
+        let value = token.value.clone();
+
+        self.state.lock().await.token = token;
+
+        Ok(value)
+    }
+
+    /// Copy of the subscription with the given ID if the secret matches, and its position for `update_subscription`.
+    pub(crate) async fn checked_subscription(&self, subscription_id: &str, secret: &str) -> Result<(usize, Subscription)> {
+        let mut tx = self.state.lock().await;
+        let subscription = tx.check_client_secret(subscription_id, secret)?.clone();
+        let index = tx.subscriptions.iter().position(|s| s.id() == subscription.id()).context("Unknown subscription")?;
+
+        Ok((index, subscription))
+    }
+
+    /// Stores the subscription refreshed without holding the state.
+    pub(crate) async fn update_subscription(&self, index: usize, subscription: Subscription) {
+        if let Some(current) = self.state.lock().await.subscriptions.get_mut(index) {
+            current.update(subscription);
+        }
+    }
+
+    /// Copies of the subscriptions matching `filter` with their positions, refreshed without holding the state.
+    async fn subscriptions_where(&self, filter: impl Fn(&Subscription) -> bool) -> Vec<(usize, Subscription)> {
+        self.state
+            .lock()
+            .await
+            .subscriptions
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| filter(s))
+            .map(|(i, s)| (i, s.clone()))
+            .collect()
+    }
+
+    /// Subscribes to messages of every routed channel. Failed subscriptions stay nil, `manage_subscriptions` retries them.
+    pub async fn init_subscriptions(&self) {
+        let token = match self.get_token().await {
+            Ok(t) => t,
+            Err(e) => {
+                error!("Failed to get token to init subscriptions: {:#}", e);
+                return;
+            },
+        };
+
+        for (index, mut subscription) in self.subscriptions_where(|_| true).await {
+            match subscription.init(&self.client, &self.config, &token, false).await {
+                Ok(()) => self.update_subscription(index, subscription).await,
+                Err(e) => error!("Failed to init subscription to {}: {:#}", subscription.route().messages_resource(), e),
+            }
+        }
+    }
+
+    /// Renews subscriptions before they expire, recreating ones which can't be renewed.
+    /// Lifecycle notifications may not arrive at all (e.g. while the service is down), so they aren't relied on.
+    pub async fn manage_subscriptions(&self, catalog: &Catalog, notifier: &Notifier) -> Result<()> {
+        loop {
+            sleep(Duration::from_secs(SUBSCRIPTIONS_CHECK_INTERVAL_SECS)).await;
+
+            let pending = self.subscriptions_where(Subscription::needs_renewal).await;
+
+            if pending.is_empty() {
+                continue;
+            }
+
+            let token = match self.get_token().await {
+                Ok(t) => t,
+                Err(e) => {
+                    error!("Failed to get token to renew subscriptions: {:#}", e);
+                    continue;
+                },
+            };
+
+            for (index, mut subscription) in pending {
+                let result = subscription.refresh(&self.client, &self.config, &token).await;
+                let project_key = subscription.route().project_key.clone();
+                let resource = subscription.route().messages_resource();
+
+                // Failures are stored too, they are shown by the admin API
+                self.update_subscription(index, subscription).await;
+
+                match result {
+                    Ok(()) => info!("Subscription to {} renewed", resource),
+                    Err(e) => {
+                        error!("Failed to renew subscription to {}: {:#}", resource, e);
+
+                        let alert = Alert::new(format!("subscription:{project_key}"), "alert_subscription_failed")
+                            .value("project", &project_key)
+                            .error(&e);
+
+                        notifier.alert(self, catalog, alert).await;
+                    },
+                }
+            }
+        }
+    }
+
+    pub(crate) async fn subscriptions_status(&self) -> Vec<SubscriptionStatus> {
+        self.state_status().await.subscriptions
+    }
+
+    pub(crate) async fn state_status(&self) -> StateStatus {
+        self.state.lock().await.status()
+    }
+
+    /// Sends authentication link like `request_authentication`, returns `false` if it failed.
+    async fn try_request_authentication(&self, catalog: &Catalog) -> bool {
+        match self.request_authentication(catalog).await {
+            Ok(()) => true,
+            Err(e) => {
+                error!("Failed to send authentication link: {:#}", e);
+                false
+            },
+        }
+    }
+
+    /// Sends authentication link to the Teams user, the delegated token is granted when the user follows it.
+    async fn request_authentication(&self, catalog: &Catalog) -> Result<()> {
+        let token = self.get_token().await?;
+        let auth_state = Uuid::new_v4();
+
+        self.state.lock().await.auth_state = auth_state;
+        self.send_auth_link(&token, auth_state, catalog).await
+    }
+
+    async fn send_auth_link(&self, access_token: &str, auth_state: Uuid, catalog: &Catalog) -> Result<()> {
+        let auth_url = format!("https://login.microsoftonline.com/{}/oauth2/v2.0/authorize?client_id={}&scope=offline_access%20ChannelMessage.Send%20ChannelMessage.ReadWrite&response_type=code&redirect_uri={}&response_mode=form_post&state={}", self.config.tenant_id, self.config.client_id, self.config.oauth_url, auth_state);
+        
+        let content = fill_html_template(catalog.get(None, "auth_email_body"), &[("url", &auth_url)]);
+
+        self.send_mail(access_token, std::slice::from_ref(&self.config.teams_user), catalog.get(None, "auth_email_subject"), &content).await
+    }
+
+    /// Sends HTML email on behalf of `TEAMS_USER` with the application token.
+    pub(crate) async fn send_mail(&self, access_token: &str, recipients: &[String], subject: &str, content: &str) -> Result<()> {
+        let recipients: Vec<_> = recipients
+            .iter()
+            .map(|address| json!({ "emailAddress": { "address": address } }))
+            .collect();
+
+        let payload = json!({
+            "message": {
+                "subject": subject,
+                "body": {
+                    "contentType": "html",
+                    "content": content,
+                },
+                "toRecipients": recipients
+            }
+        });
+        
+        self.client
+            .post(format!("https://graph.microsoft.com/v1.0/users/{}/sendMail", self.config.teams_user))
+            .bearer_auth(access_token)
+            .json(&payload)
+            .send_resilient()
+            .await
+            .context("Failed to send email")?
+            .error_for_status()
+            .context("Send email request bad status")?;
+
+        Ok(())
+    }
+
+    /// Posts a new message to the channel on behalf of `TEAMS_USER`.
+    pub(crate) async fn post_message(&self, group_id: &str, channel_id: &str, content: &str) -> Result<()> {
+        let token = self.granted_token.read().await.get()?;
+
+        let payload = json!({
+            "body": {
+                "contentType": "html",
+                "content": content
+            }
+        });
+
+        let builder = self.client
+            .post(format!("https://graph.microsoft.com/v1.0/teams/{}/channels/{}/messages", group_id, channel_id))
+            .bearer_auth(token)
+            .json(&payload);
+
+        builder
+            .send_resilient()
+            .await
+            .context("Failed to send post message request")?
+            .error_for_status()
+            .context("Post message request bad status")?;
+
+        Ok(())
     }
 
-    pub(crate) async fn set_delegated_token(&self, code: String) -> Result<()> {
+    pub(crate) async fn set_delegated_token(&self, store: &Store, code: String) -> Result<()> {
         let mut tx = self.granted_token.write().await;
-        tx.set_first_time(&self.client, &self.config, code).await
+        tx.set_first_time(&self.client, &self.config, code).await?;
+        tx.save(store, &self.config).await.context("Failed to save delegated token")
+    }
+
+    /// Sign-in link of card buttons, Microsoft posts the code to `TEAMS_ACTION_URL` with the `state`.
+    pub(crate) fn action_sign_in_url(&self, state: &str) -> Result<String> {
+        let url = reqwest::Url::parse_with_params(
+            &format!("https://login.microsoftonline.com/{}/oauth2/v2.0/authorize", self.config.tenant_id),
+            &[
+                ("client_id", self.config.client_id.as_str()),
+                ("scope", "User.Read"),
+                ("response_type", "code"),
+                ("redirect_uri", self.config.action_url.as_str()),
+                ("response_mode", "form_post"),
+                ("state", state),
+            ],
+        )?;
+
+        Ok(url.into())
+    }
+
+    /// User who signed in via `action_sign_in_url`.
+    pub(crate) async fn get_signed_in_user(&self, code: &str) -> Result<MsUser> {
+        #[derive(Deserialize)]
+        struct TokenResponse {
+            access_token: String,
+        }
+
+        let form = [
+            ("client_id", self.config.client_id.as_str()),
+            ("scope", "User.Read"),
+            ("code", code),
+            ("redirect_uri", self.config.action_url.as_str()),
+            ("grant_type", "authorization_code"),
+        ];
+
+        let token = self.client
+            .post(format!("https://login.microsoftonline.com/{}/oauth2/v2.0/token", self.config.tenant_id))
+            .form(&form)
+            .send_resilient()
+            .await
+            .context("Failed to send get token request")?
+            .error_for_status()
+            .context("Get token request bad status")?
+            .json::<TokenResponse>()
+            .await
+            .context("Parse get token response")?;
+
+        self.client
+            .get("https://graph.microsoft.com/v1.0/me?$select=id,mail,displayName,preferredLanguage")
+            .bearer_auth(token.access_token)
+            .send_resilient()
+            .await
+            .context("Failed to send get signed in user request")?
+            .error_for_status()
+            .context("Get signed in user request bad status")?
+            .json::<MsUser>()
+            .await
+            .context("Parse get signed in user response")
     }
 
-    pub async fn manage_granted_token(&self) -> Result<()> {
+    /// Restores the delegated token saved by the previous run and keeps it fresh. The authentication link
+    /// is sent only if there is no saved token or Microsoft rejects it.
+    pub async fn manage_granted_token(&self, store: &Store, catalog: &Catalog, notifier: &Notifier) -> Result<()> {
+        let restored = self.granted_token
+            .write()
+            .await
+            .load(store, &self.config)
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Failed to restore delegated token: {:#}", e);
+                false
+            });
+
+        // The link is sent again if sending fails, until the user follows it
+        let mut link_sent = restored || self.try_request_authentication(catalog).await;
         let mut backoff_time: u64 = 1;
-        let mut token_is_empty = true;
-    
+
         loop {
             sleep(Duration::from_secs(backoff_time)).await;
-            if token_is_empty {
-                token_is_empty = self.granted_token.read().await.get().unwrap_or_default().is_empty();
+
+            if !self.granted_token.read().await.has_refresh_token() {
+                if !link_sent {
+                    link_sent = self.try_request_authentication(catalog).await;
+                }
+
+                backoff_time = if link_sent { 1 } else { (backoff_time * 2).clamp(GRANTED_TOKEN_RETRY_SECS, GRANTED_TOKEN_MAX_RETRY_SECS) };
+                continue;
             }
 
-            if !token_is_empty {
-                match self.granted_token.write().await.refresh_and_get_expiration_time(&self.client, &self.config).await {
-                    Ok(expires_in) => {
-                        backoff_time = expires_in / 2;
-                    },
-                    Err(_) => {
-                        backoff_time /= 2; // Exponential backoff
-                        ensure!(backoff_time > 1, "Too many retries");
+            let mut tx = self.granted_token.write().await;
+
+            match tx.refresh_and_get_expiration_time(&self.client, &self.config).await {
+                Ok(expires_in) => {
+                    backoff_time = expires_in / 2;
+
+                    if let Err(e) = tx.save(store, &self.config).await {
+                        error!("Failed to save delegated token: {:#}", e);
                     }
-                }
+                },
+                Err(e) if is_invalid_grant(&e) => {
+                    warn!("Delegated token is rejected, authentication is requested: {:#}", e);
+                    tx.clear();
+                    drop(tx);
+
+                    if let Err(e) = GrantedToken::remove(store).await {
+                        error!("Failed to remove delegated token: {:#}", e);
+                    }
+
+                    let alert = Alert::new("delegated_token", "alert_token_rejected")
+                        .value("user", &self.config.teams_user)
+                        .error(&e);
+
+                    notifier.alert(self, catalog, alert).await;
+
+                    link_sent = self.try_request_authentication(catalog).await;
+                    backoff_time = 1;
+                },
+                Err(e) => {
+                    error!("Failed to refresh delegated token: {:#}", e);
+                    // Retry before the access token expires
+                    backoff_time = (backoff_time / 2).max(GRANTED_TOKEN_RETRY_SECS);
+
+                    let expired = tx.get().is_err();
+                    drop(tx);
+
+                    if expired {
+                        let alert = Alert::new("delegated_token", "alert_token_expired")
+                            .value("user", &self.config.teams_user)
+                            .error(&e);
+
+                        notifier.alert(self, catalog, alert).await;
+                    }
+                },
             }
         }
     }
 
-    pub(crate) async fn reply_to_issue(&self, message_id: &str, reply_body: &str) -> Result<MsGraphMessage> {
+    pub(crate) async fn reply_to_issue(&self, route: &Route, message_id: &str, reply_body: &str, parts: &ReplyParts) -> Result<MsGraphMessage> {
         let token = self.granted_token.read().await.get()?;
 
         let payload = json!(
@@ -166,16 +544,20 @@ impl MSGraphAPI {
                 "body": {
                     "contentType": "html",
                     "content": reply_body
-                }
+                },
+                "mentions": parts.mentions,
+                "hostedContents": parts.hosted_contents,
+                "attachments": parts.attachments
             }        
         );
 
         let builder = self.client
-            .post(format!("https://graph.microsoft.com/v1.0/teams/{}/channels/{}/messages/{}/replies", self.config.group_id, self.config.channel_id, message_id))
+            .post(format!("https://graph.microsoft.com/v1.0/teams/{}/channels/{}/messages/{}/replies", route.group_id, route.channel_id, message_id))
             .bearer_auth(token)
             .json(&payload);
 
-        let response = send_with_throttle_retry(builder)
+        let response = builder
+            .send_resilient()
             .await
             .context("Failed to send reply to issue request")?
             .error_for_status()
@@ -187,7 +569,7 @@ impl MSGraphAPI {
         Ok(response)
     }
 
-    pub(crate) async fn edit_reply(&self, message_id: &str, reply_id: &str, reply_body: &str) -> Result<()> {
+    pub(crate) async fn edit_reply(&self, route: &Route, message_id: &str, reply_id: &str, reply_body: &str, parts: &ReplyParts) -> Result<()> {
         let token = self.granted_token.read().await.get()?;
 
         let payload = json!(
@@ -195,16 +577,20 @@ impl MSGraphAPI {
                 "body": {
                     "contentType": "html",
                     "content": reply_body
-                }
+                },
+                "mentions": parts.mentions,
+                "hostedContents": parts.hosted_contents,
+                "attachments": parts.attachments
             }        
         );
 
         let builder = self.client
-            .patch(format!("https://graph.microsoft.com/v1.0/teams/{}/channels/{}/messages/{}/replies/{}", self.config.group_id, self.config.channel_id, message_id, reply_id))
+            .patch(format!("https://graph.microsoft.com/v1.0/teams/{}/channels/{}/messages/{}/replies/{}", route.group_id, route.channel_id, message_id, reply_id))
             .bearer_auth(token)
             .json(&payload);
 
-        send_with_throttle_retry(builder)
+        builder
+            .send_resilient()
             .await
             .context("Failed to send reply edit request")?
             .error_for_status()
@@ -212,4 +598,64 @@ impl MSGraphAPI {
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Edits the bot reply of the given kind in the thread, or posts it if there is none yet.
+    pub(crate) async fn upsert_bot_reply(
+        &self,
+        store: &Store,
+        route: &Route,
+        message_id: &str,
+        kind: &str,
+        reply_body: &str,
+        parts: &ReplyParts,
+    ) -> Result<()> {
+        if let Some(reply_id) = store.get_bot_reply(&route.channel_id, message_id, kind).await? {
+            match self.edit_reply(route, message_id, &reply_id, reply_body, parts).await {
+                Ok(()) => return Ok(()),
+                // Reply was deleted, post a new one
+                Err(e) if is_not_found(&e) => (),
+                Err(e) => return Err(e),
+            }
+        }
+
+        let reply = self.reply_to_issue(route, message_id, reply_body, parts).await?;
+
+        store.set_bot_reply(&route.channel_id, message_id, kind, &reply.id).await
+    }
+
+    pub(crate) async fn get_reply(&self, route: &Route, message_id: &str, reply_id: &str) -> Result<MsGraphMessage> {
+        let token = self.granted_token.read().await.get()?;
+
+        let builder = self.client
+            .get(format!("https://graph.microsoft.com/v1.0/teams/{}/channels/{}/messages/{}/replies/{}", route.group_id, route.channel_id, message_id, reply_id))
+            .bearer_auth(token);
+
+        builder
+            .send_resilient()
+            .await
+            .context("Failed to send get reply request")?
+            .error_for_status()
+            .context("Get reply request bad status")?
+            .json::<MsGraphMessage>()
+            .await
+            .context("Parse get reply response")
+    }
+
+    /// Deletes reply, Teams shows "This message has been deleted" in its place.
+    pub(crate) async fn delete_reply(&self, route: &Route, message_id: &str, reply_id: &str) -> Result<()> {
+        let token = self.granted_token.read().await.get()?;
+
+        let builder = self.client
+            .post(format!("https://graph.microsoft.com/v1.0/teams/{}/channels/{}/messages/{}/replies/{}/softDelete", route.group_id, route.channel_id, message_id, reply_id))
+            .bearer_auth(token);
+
+        builder
+            .send_resilient()
+            .await
+            .context("Failed to send delete reply request")?
+            .error_for_status()
+            .context("Delete reply request bad status")?;
+
+        Ok(())
+    }
+}
@@ -1,15 +1,41 @@
-use anyhow::{bail, ensure, Context, Result};
+use anyhow::{anyhow, bail, ensure, Context, Result};
 use chrono::{DateTime, Utc};
 use reqwest::{Client, Response, StatusCode};
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use tracing::warn;
 use uuid::Uuid;
 
+use crate::{http_client::model::ResilientRequest, routes::model::Route};
+
 use super::cfg::Config;
 
+/// Lifetime of a new or renewed subscription.
+const LIFETIME_HOURS: i64 = 3;
+/// Subscription is renewed when it expires sooner than this.
+const RENEW_BEFORE_MINS: i64 = 30;
+
+/// Graph API subscription to messages of a routed channel.
+#[derive(Clone)]
 pub struct Subscription {
+    route: Route,
     subscription_id: Uuid,
     subscription_secret: Uuid,
+    expires_at: Option<DateTime<Utc>>,
+    last_notification_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+}
+
+/// State of the subscription shown by the admin API.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SubscriptionStatus {
+    pub(crate) id: Uuid,
+    pub(crate) resource: String,
+    pub(crate) project_key: String,
+    pub(crate) expires_at: Option<DateTime<Utc>>,
+    pub(crate) last_notification_at: Option<DateTime<Utc>>,
+    /// Error of the last renewal, cleared by a successful one.
+    pub(crate) last_error: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -30,8 +56,12 @@ struct RenewSubsciptionRequest {
 }
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct NewSubsciptionResponse {
     id: Uuid,
+    #[serde(default)]
+    resource: String,
+    expiration_date_time: Option<DateTime<Utc>>,
 }
 
 #[derive(Deserialize)]
@@ -40,84 +70,134 @@ struct ActiveSubsciptionResponse {
 }
 
 impl Subscription {
-    pub(crate) fn new() -> Self {
-        Self { subscription_id: Uuid::nil(), subscription_secret: Uuid::nil() }
+    pub(crate) fn new(route: Route) -> Self {
+        Self {
+            route,
+            subscription_id: Uuid::nil(),
+            subscription_secret: Uuid::nil(),
+            expires_at: None,
+            last_notification_at: None,
+            last_error: None,
+        }
+    }
+
+    pub(crate) fn route(&self) -> &Route {
+        &self.route
+    }
+
+    pub(crate) fn id(&self) -> Uuid {
+        self.subscription_id
     }
 
     pub async fn init(&mut self, client: &Client, config: &Config, access_token: &str, repeated: bool) -> Result<()> {
         let subscription_secret = Uuid::new_v4();
-        let response = add_subscription_response(config, client, access_token, subscription_secret).await?;
+        let resource = self.route.messages_resource();
+        let response = add_subscription_response(config, client, access_token, &resource, subscription_secret).await?;
 
         if response.status().is_success() {
-            self.subscription_secret = subscription_secret;
-            self.subscription_id = response.json::<NewSubsciptionResponse>().await.context("Failed to retrieve subscription ID")?.id;
+            self.created(response, subscription_secret).await?;
         } else if response.status() == StatusCode::FORBIDDEN {
             if repeated {
                 bail!("Failed to kill active subscription");
             } else {
-                kill_active_subscription(client, access_token).await?;
+                kill_active_subscription(client, access_token, &resource).await?;
 
-                let response = add_subscription_response(config, client, access_token, subscription_secret).await?;
+                let response = add_subscription_response(config, client, access_token, &resource, subscription_secret).await?;
 
                 ensure!(response.status().is_success(), response.text().await?);
 
-                self.subscription_secret = subscription_secret;
-                self.subscription_id = response.json::<NewSubsciptionResponse>().await.context("Failed to retrieve subscription ID")?.id;
+                self.created(response, subscription_secret).await?;
             };
         } else {
             bail!(response.text().await?)
         }
 
-        let auth_url = format!("https://login.microsoftonline.com/{}/oauth2/v2.0/authorize?client_id={}&scope=offline_access%20ChannelMessage.Send%20ChannelMessage.ReadWrite&response_type=code&redirect_uri={}&response_mode=form_post&state={}", config.tenant_id, config.client_id, config.oauth_url, subscription_secret);
-        
-        let content = format!("Please, go to email below<BR><a href=\"{}\">{}</a>", auth_url, auth_url);
-        
-        let payload = json!({
-            "message": {
-                "subject": "Jira vs Teams authentication link",
-                "body": {
-                    "contentType": "html",
-                    "content": content,
-                },
-                "toRecipients": [
-                    {
-                        "emailAddress": {
-                            "address": config.teams_user
-                        }
-                    }
-                ]
-            }
-        });
-        
-        client
-            .post(format!("https://graph.microsoft.com/v1.0/users/{}/sendMail", config.teams_user))
-            .bearer_auth(access_token)
-            .json(&payload)
-            .send()
-            .await
-            .context("Failed to send email")?;
+        Ok(())
+    }
+
+    async fn created(&mut self, response: Response, subscription_secret: Uuid) -> Result<()> {
+        let response = response.json::<NewSubsciptionResponse>().await.context("Failed to retrieve subscription ID")?;
+
+        self.subscription_secret = subscription_secret;
+        self.subscription_id = response.id;
+        self.expires_at = response.expiration_date_time;
 
         Ok(())
     }
 
-    pub(crate) async fn renew(&mut self, client: &Client, access_token: &str, subsciption_id: &str) -> Result<()> {
+    pub(crate) async fn renew(&mut self, client: &Client, access_token: &str) -> Result<()> {
         let req = RenewSubsciptionRequest {
-            expiration_date_time: Utc::now() + chrono::Duration::try_hours(3).unwrap(),
+            expiration_date_time: Utc::now() + chrono::Duration::hours(LIFETIME_HOURS),
         };
 
-        client
-            .patch(format!("https://graph.microsoft.com/v1.0/subscriptions/{}", subsciption_id))
+        let response = client
+            .patch(format!("https://graph.microsoft.com/v1.0/subscriptions/{}", self.subscription_id))
             .bearer_auth(access_token)
             .json(&req)
-            .send()
+            .send_resilient()
             .await
             .context("Failed to send renew subscription request")?
             .error_for_status()
-            .context("Renew subscription request bad status")?;
+            .context("Renew subscription request bad status")?
+            .json::<NewSubsciptionResponse>()
+            .await
+            .context("Parse renew subscription response")?;
+
+        self.expires_at = response.expiration_date_time.or(Some(req.expiration_date_time));
 
         Ok(())
     }
 
+    /// Whether the subscription is missing or expires soon.
+    pub(crate) fn needs_renewal(&self) -> bool {
+        self.subscription_id.is_nil()
+            || self.expires_at.is_none_or(|t| t - chrono::Duration::minutes(RENEW_BEFORE_MINS) <= Utc::now())
+    }
+
+    /// Renews the subscription, or creates a new one if it can't be renewed (e.g. it has already expired).
+    pub(crate) async fn refresh(&mut self, client: &Client, config: &Config, access_token: &str) -> Result<()> {
+        let renewed = match self.subscription_id.is_nil() {
+            true => Err(anyhow!("No subscription")),
+            false => self.renew(client, access_token).await,
+        };
+
+        let result = match renewed {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                warn!("Failed to renew subscription to {}, creating a new one: {:#}", self.route.messages_resource(), e);
+                self.init(client, config, access_token, false).await
+            },
+        };
+
+        self.last_error = result.as_ref().err().map(|e| format!("{:#}", e));
+
+        result
+    }
+
+    /// Takes the result of a refresh made on a copy of the subscription, keeping notifications received meanwhile.
+    pub(crate) fn update(&mut self, refreshed: Subscription) {
+        let last_notification_at = self.last_notification_at.max(refreshed.last_notification_at);
+
+        *self = refreshed;
+        self.last_notification_at = last_notification_at;
+    }
+
+    /// Records a change notification received by the subscription.
+    pub(crate) fn notified(&mut self) {
+        self.last_notification_at = Some(Utc::now());
+    }
+
+    pub(crate) fn status(&self) -> SubscriptionStatus {
+        SubscriptionStatus {
+            id: self.subscription_id,
+            resource: self.route.messages_resource(),
+            project_key: self.route.project_key.clone(),
+            expires_at: self.expires_at,
+            last_notification_at: self.last_notification_at,
+            last_error: self.last_error.clone(),
+        }
+    }
+
     pub(crate) fn check_client_secret(&self, secret: &str) -> Result<()> {
         let secret_uuid = Uuid::try_parse(secret)?;
         ensure!(secret_uuid == self.subscription_secret, "Incorrect secret");
@@ -125,22 +205,23 @@ impl Subscription {
     }
 }
 
-async fn kill_active_subscription(client: &Client, access_token: &str) -> Result<()> {
+/// Deletes active subscription to the same resource, if any.
+async fn kill_active_subscription(client: &Client, access_token: &str, resource: &str) -> Result<()> {
     let response = client
         .get("https://graph.microsoft.com/v1.0/subscriptions/")
         .bearer_auth(access_token)
-        .send()
+        .send_resilient()
         .await
         .context("Failed to send get subscription request")?;
 
     if response.status().is_success()
         && let Ok(s) = response.json::<ActiveSubsciptionResponse>().await
-        && let Some(r) = s.value.first() 
+        && let Some(r) = s.value.iter().find(|r| same_resource(&r.resource, resource))
     {
         client
             .delete(format!("https://graph.microsoft.com/v1.0/subscriptions/{}", r.id))
             .bearer_auth(access_token)
-            .send()
+            .send_resilient()
             .await
             .context("Failed to send delete subscription request")?
             .error_for_status()
@@ -150,13 +231,17 @@ async fn kill_active_subscription(client: &Client, access_token: &str) -> Result
     Ok(())
 }
 
-async fn add_subscription_response(config: &Config, client: &Client, access_token: &str, subscription_secret: Uuid) -> Result<Response> {
+fn same_resource(left: &str, right: &str) -> bool {
+    left.trim_matches('/').eq_ignore_ascii_case(right.trim_matches('/'))
+}
+
+async fn add_subscription_response(config: &Config, client: &Client, access_token: &str, resource: &str, subscription_secret: Uuid) -> Result<Response> {
     let req = NewSubsciptionRequest {
-        change_type: String::from("created,updated"),
+        change_type: String::from("created,updated,deleted"),
         notification_url: config.notification_url.clone(),
         lifecycle_notification_url: config.lifecycle_notification_url.clone(),
-        resource: format!("/teams/{}/channels/{}/messages", config.group_id, config.channel_id),
-        expiration_date_time: Utc::now() + chrono::Duration::try_hours(3).unwrap(),
+        resource: resource.to_string(),
+        expiration_date_time: Utc::now() + chrono::Duration::hours(LIFETIME_HOURS),
         client_state: subscription_secret,
     };
 
@@ -164,7 +249,7 @@ async fn add_subscription_response(config: &Config, client: &Client, access_toke
         .post("https://graph.microsoft.com/v1.0/subscriptions")
         .bearer_auth(access_token)
         .json(&req)
-        .send()
+        .send_resilient()
         .await
         .context("Failed to send new subscription request")
-}
\ No newline at end of file
+}
@@ -4,6 +4,8 @@ use anyhow::{ensure, Context, Result};
 use reqwest::Client;
 use serde::Deserialize;
 
+use crate::http_client::model::ResilientRequest;
+
 use super::cfg::Config;
 
 pub struct ApplicationToken {
@@ -22,7 +24,8 @@ impl ApplicationToken {
         Ok(self.value.clone())
     }
 
-    pub async fn renew(&mut self, client: &Client, config: &Config) -> Result<String> {
+    /// Requests a new token. The caller stores it, so the state isn't held during the request.
+    pub async fn request(client: &Client, config: &Config) -> Result<Self> {
 
         #[derive(Deserialize)]
         struct TokenResponse {
@@ -38,7 +41,7 @@ impl ApplicationToken {
                 ("client_id", &config.client_id),
                 ("client_secret", &config.client_secret),
             ])
-            .send()
+            .send_resilient()
             .await
             .context("Failed to send get token request")?
             .error_for_status()
@@ -47,9 +50,9 @@ impl ApplicationToken {
             .await
             .context("Parse get token response")?;
 
-        self.value = token.access_token;
-        self.expires_at = Instant::now() + Duration::from_secs(token.expires_in / 2);
-
-        Ok(self.value.clone())
+        Ok(Self {
+            value: token.access_token,
+            expires_at: Instant::now() + Duration::from_secs(token.expires_in / 2),
+        })
     }
 }
\ No newline at end of file
@@ -0,0 +1,48 @@
+use std::str::FromStr;
+
+use envconfig::Envconfig;
+
+use crate::jira_api::cfg::parse_list;
+
+#[derive(Envconfig, Clone)]
+pub struct Config {
+    /// Sends alerts about sync failures to `ALERT_EMAILS` and/or the ops channel.
+    #[envconfig(from = "ALERT_ENABLED", default = "false")]
+    pub(crate) enabled: bool,
+    /// Recipients of alert emails, comma separated or JSON array.
+    #[envconfig(from = "ALERT_EMAILS", default = "")]
+    pub(crate) emails: EmailList,
+    /// Ops channel for alerts, posted on behalf of `TEAMS_USER`.
+    #[envconfig(from = "ALERT_GROUP_ID", default = "")]
+    pub(crate) group_id: String,
+    #[envconfig(from = "ALERT_CHANNEL_ID", default = "")]
+    pub(crate) channel_id: String,
+    /// Failed events within `ALERT_FAILURE_WINDOW_MINS` which raise an alert.
+    #[envconfig(from = "ALERT_FAILURE_THRESHOLD", default = "5")]
+    pub(crate) failure_threshold: usize,
+    #[envconfig(from = "ALERT_FAILURE_WINDOW_MINS", default = "15")]
+    pub(crate) failure_window_mins: u64,
+    /// The same alert isn't repeated within this period.
+    #[envconfig(from = "ALERT_COOLDOWN_MINS", default = "60")]
+    pub(crate) cooldown_mins: u64,
+    /// Limit of all alerts per hour.
+    #[envconfig(from = "ALERT_MAX_PER_HOUR", default = "10")]
+    pub(crate) max_per_hour: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct EmailList(Vec<String>);
+
+impl EmailList {
+    pub(crate) fn as_slice(&self) -> &[String] {
+        &self.0
+    }
+}
+
+impl FromStr for EmailList {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_list(s).map(Self)
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::{HashMap, VecDeque};
+
+use anyhow::Result;
+use tokio::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    i18n::model::Catalog,
+    ms_graph_api::model::MSGraphAPI,
+    utils::{fill_template, html_escape},
+};
+
+use super::cfg::Config;
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+
+/// Tells the ops about sync failures by email and/or a post to the ops channel.
+/// Alerts are de-duplicated by key and rate limited, so an outage doesn't flood the inbox.
+pub struct Notifier {
+    pub(crate) config: Config,
+    state: Mutex<NotifierState>,
+}
+
+#[derive(Default)]
+struct NotifierState {
+    /// Failed events within the window.
+    failures: VecDeque<Instant>,
+    /// Time of the last alert by key.
+    sent_by_key: HashMap<String, Instant>,
+    /// Alerts sent within the last hour.
+    sent: VecDeque<Instant>,
+}
+
+/// Problem to alert about.
+pub(crate) struct Alert {
+    /// Alerts with the same key are sent once per `ALERT_COOLDOWN_MINS`.
+    key: String,
+    /// Catalog key of the title.
+    template: &'static str,
+    values: Vec<(&'static str, String)>,
+    /// Key of the affected issue, or its ID if the key isn't known.
+    issue: Option<String>,
+    /// Error chain, outermost first.
+    errors: Vec<String>,
+}
+
+impl Alert {
+    pub(crate) fn new(key: impl Into<String>, template: &'static str) -> Self {
+        Self { key: key.into(), template, values: Vec::new(), issue: None, errors: Vec::new() }
+    }
+
+    pub(crate) fn value(mut self, name: &'static str, value: impl ToString) -> Self {
+        self.values.push((name, value.to_string()));
+        self
+    }
+
+    pub(crate) fn issue(mut self, issue: Option<String>) -> Self {
+        self.issue = issue;
+        self
+    }
+
+    pub(crate) fn error(mut self, error: &anyhow::Error) -> Self {
+        self.errors = error.chain().map(ToString::to_string).collect();
+        self
+    }
+}
+
+impl Notifier {
+    pub fn new(config: Config) -> Self {
+        Self { config, state: Mutex::new(NotifierState::default()) }
+    }
+
+    /// Counts a failed event and alerts when `ALERT_FAILURE_THRESHOLD` events fail within `ALERT_FAILURE_WINDOW_MINS`.
+    pub(crate) async fn record_failure(&self, microsoft: &MSGraphAPI, catalog: &Catalog, error: &anyhow::Error, issue: Option<String>) {
+        if !self.config.enabled {
+            return;
+        }
+
+        let window = Duration::from_secs(self.config.failure_window_mins * 60);
+        let now = Instant::now();
+
+        let count = {
+            let mut state = self.state.lock().await;
+
+            state.failures.push_back(now);
+            state.failures.retain(|t| now.duration_since(*t) < window);
+
+            let count = state.failures.len();
+
+            if count < self.config.failure_threshold.max(1) {
+                return;
+            }
+
+            // Next alert needs as many new failures
+            state.failures.clear();
+            count
+        };
+
+        let alert = Alert::new("sync_failures", "alert_sync_failures")
+            .value("count", count)
+            .value("minutes", self.config.failure_window_mins)
+            .issue(issue)
+            .error(error);
+
+        self.alert(microsoft, catalog, alert).await;
+    }
+
+    /// Sends the alert unless the same one was sent recently or too many alerts were sent within the hour.
+    /// Failures to send are only logged.
+    pub(crate) async fn alert(&self, microsoft: &MSGraphAPI, catalog: &Catalog, alert: Alert) {
+        if !self.config.enabled || !self.reserve(&alert.key).await {
+            return;
+        }
+
+        match self.send(microsoft, catalog, &alert).await {
+            Ok(()) => info!("Alert {} sent", alert.key),
+            Err(e) => error!("Failed to send alert {}: {:#}", alert.key, e),
+        }
+    }
+
+    /// Records the alert as sent if it's allowed.
+    async fn reserve(&self, key: &str) -> bool {
+        let cooldown = Duration::from_secs(self.config.cooldown_mins * 60);
+        let now = Instant::now();
+        let mut state = self.state.lock().await;
+
+        if state.sent_by_key.get(key).is_some_and(|t| now.duration_since(*t) < cooldown) {
+            return false;
+        }
+
+        state.sent.retain(|t| now.duration_since(*t) < HOUR);
+
+        if state.sent.len() >= self.config.max_per_hour {
+            warn!("Alert {} is suppressed, {} alerts were sent within an hour", key, state.sent.len());
+            return false;
+        }
+
+        state.sent.push_back(now);
+        state.sent_by_key.insert(key.to_string(), now);
+
+        true
+    }
+
+    async fn send(&self, microsoft: &MSGraphAPI, catalog: &Catalog, alert: &Alert) -> Result<()> {
+        let values: Vec<(&str, &str)> = alert.values.iter().map(|(n, v)| (*n, v.as_str())).collect();
+        let title = fill_template(catalog.get(None, alert.template), &values);
+
+        let mut content = format!("<p><b>{}</b></p>", html_escape(&title));
+
+        if let Some(issue) = &alert.issue {
+            content.push_str(&format!("<p>{}</p>", html_escape(&fill_template(catalog.get(None, "alert_issue"), &[("issue", issue)]))));
+        }
+
+        if !alert.errors.is_empty() {
+            let errors: String = alert.errors.iter().map(|e| format!("<li>{}</li>", html_escape(e))).collect();
+            content.push_str(&format!("<p>{}</p><ol>{}</ol>", catalog.get(None, "alert_error"), errors));
+        }
+
+        let mut result = Ok(());
+
+        // Channel posts need the delegated token, so emails are sent as well when both are configured
+        if !self.config.group_id.is_empty() && !self.config.channel_id.is_empty() {
+            result = microsoft.post_message(&self.config.group_id, &self.config.channel_id, &content).await;
+        }
+
+        if !self.config.emails.as_slice().is_empty() {
+            let subject = fill_template(catalog.get(None, "alert_subject"), &[("title", &title)]);
+            let token = microsoft.get_token().await?;
+
+            result = result.and(microsoft.send_mail(&token, self.config.emails.as_slice(), &subject, &content).await);
+        }
+
+        result
+    }
+}
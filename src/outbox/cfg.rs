@@ -0,0 +1,15 @@
+use envconfig::Envconfig;
+
+#[derive(Envconfig, Clone)]
+pub struct Config {
+    #[envconfig(from = "OUTBOX_WORKERS", default = "4")]
+    pub(crate) workers: usize,
+    #[envconfig(from = "OUTBOX_MAX_ATTEMPTS", default = "8")]
+    pub(crate) max_attempts: u32,
+    #[envconfig(from = "OUTBOX_BACKOFF_SECS", default = "10")]
+    pub(crate) backoff_secs: u64,
+    #[envconfig(from = "OUTBOX_MAX_BACKOFF_SECS", default = "3600")]
+    pub(crate) max_backoff_secs: u64,
+    #[envconfig(from = "OUTBOX_POLL_INTERVAL_SECS", default = "5")]
+    pub(crate) poll_interval_secs: u64,
+}
@@ -0,0 +1,184 @@
+use anyhow::{Context, Result};
+use axum::body::Bytes;
+use chrono::Utc;
+use tokio::{sync::Notify, time::{sleep, Duration}};
+use tracing::{error, info, info_span, warn, Instrument, Span};
+
+use crate::{
+    logging::model::CorrelationId,
+    metrics::model::record_webhook,
+    notifier::model::Alert,
+    server::{handlers::{helpers::{get_group_id_and_channel_id, get_message_id_and_reply_id}, jira::handle_jira_request, teams::{self, handle_teams_request}}, AppStateShared},
+    store::event::{Event, EventSource},
+};
+
+use super::cfg::Config;
+
+/// Queue of accepted webhooks. Events are persisted in the store first and then
+/// processed by a pool of workers, so they survive failures and restarts.
+pub struct Outbox {
+    pub(crate) config: Config,
+    wakeup: Notify,
+}
+
+impl Outbox {
+    pub fn new(config: Config) -> Self {
+        Self { config, wakeup: Notify::new() }
+    }
+
+    /// Persists webhook payload and wakes up a worker to process it.
+    pub(crate) async fn push(
+        &self,
+        state_shared: &AppStateShared,
+        source: EventSource,
+        kind: &str,
+        payload: &[u8],
+        correlation_id: &CorrelationId,
+    ) -> Result<()> {
+        state_shared.store
+            .enqueue_event(source, kind, payload, &correlation_id.0)
+            .await
+            .context("Failed to enqueue event")?;
+
+        self.wakeup.notify_one();
+
+        Ok(())
+    }
+
+    /// Runs workers until the process stops.
+    pub async fn run(state_shared: AppStateShared) -> Result<()> {
+        let requeued = state_shared.store.requeue_processing_events().await?;
+
+        if requeued > 0 {
+            info!("Requeued {} events interrupted by previous shutdown", requeued);
+        }
+
+        let workers = (0..state_shared.outbox.config.workers.max(1))
+            .map(|_| tokio::task::spawn(worker(state_shared.clone())));
+
+        futures::future::try_join_all(workers).await?;
+
+        Ok(())
+    }
+
+    fn retry_at(&self, attempts: u32) -> Option<i64> {
+        if attempts >= self.config.max_attempts {
+            return None;
+        }
+
+        let delay = self.config.backoff_secs
+            .saturating_mul(1 << attempts.saturating_sub(1).min(32))
+            .min(self.config.max_backoff_secs);
+
+        Some(Utc::now().timestamp() + delay as i64)
+    }
+}
+
+async fn worker(state_shared: AppStateShared) {
+    let poll_interval = Duration::from_secs(state_shared.outbox.config.poll_interval_secs);
+
+    loop {
+        match state_shared.store.claim_next_event().await {
+            Ok(Some(event)) => {
+                let span = event_span(&event);
+
+                if let Err(e) = process(&state_shared, event).instrument(span).await {
+                    error!("Failed to save event result: {:#}", e);
+                }
+            },
+            Ok(None) => {
+                tokio::select! {
+                    _ = state_shared.outbox.wakeup.notified() => (),
+                    _ = sleep(poll_interval) => (),
+                }
+            },
+            Err(e) => {
+                error!("Failed to claim event: {:#}", e);
+                sleep(poll_interval).await;
+            },
+        }
+    }
+}
+
+async fn process(state_shared: &AppStateShared, event: Event) -> Result<()> {
+    match dispatch(state_shared.clone(), &event).await {
+        Ok(()) => {
+            record_webhook(event.source.as_str(), &event.kind, "processed");
+            state_shared.store.complete_event(event.id).await
+        },
+        Err(e) => {
+            let attempts = event.attempts + 1;
+            let retry_at = state_shared.outbox.retry_at(attempts);
+
+            record_webhook(event.source.as_str(), &event.kind, if retry_at.is_some() { "retry" } else { "dead" });
+
+            if retry_at.is_some() {
+                warn!("Event {} from {} failed (attempt {}): {:#}", event.id, event.source.as_str(), attempts, e);
+            } else {
+                error!("Event {} from {} is dead after {} attempts: {:#}", event.id, event.source.as_str(), attempts, e);
+            }
+
+            state_shared.store.fail_event(event.id, &format!("{:#}", e), retry_at).await?;
+
+            let issue = event_issue(state_shared, &event).await;
+            let notifier = &state_shared.notifier;
+
+            notifier.record_failure(&state_shared.microsoft, &state_shared.i18n, &e, issue.clone()).await;
+
+            if retry_at.is_none() {
+                let alert = Alert::new("event_dead", "alert_event_dead")
+                    .value("attempts", attempts)
+                    .issue(issue)
+                    .error(&e);
+
+                notifier.alert(&state_shared.microsoft, &state_shared.i18n, alert).await;
+            }
+
+            Ok(())
+        },
+    }
+}
+
+/// Key of the issue the event is about, or its ID for Teams events.
+async fn event_issue(state_shared: &AppStateShared, event: &Event) -> Option<String> {
+    match event.source {
+        EventSource::Jira => {
+            let payload = serde_json::from_slice::<serde_json::Value>(&event.payload).ok()?;
+            let issue = payload.get("issue")?;
+
+            issue.get("key").or_else(|| issue.get("id")).and_then(|v| v.as_str()).map(String::from)
+        },
+        EventSource::Teams => {
+            let request = serde_json::from_slice::<teams::Request>(&event.payload).ok()?;
+            let resource = &request.value?.into_iter().next()?.resource;
+            let (message_id, _) = get_message_id_and_reply_id(resource);
+            let (_, channel_id) = get_group_id_and_channel_id(resource)?;
+
+            state_shared.store.get_issue_id(&channel_id, &message_id?).await.ok().flatten()
+        },
+    }
+}
+
+/// Span of the event processing, carrying the correlation ID of the webhook.
+pub(crate) fn event_span(event: &Event) -> Span {
+    info_span!(
+        "event",
+        event_id = event.id,
+        source = event.source.as_str(),
+        correlation_id = event.correlation_id.as_deref().unwrap_or_default(),
+    )
+}
+
+pub(crate) async fn dispatch(state_shared: AppStateShared, event: &Event) -> Result<()> {
+    match event.source {
+        EventSource::Jira => {
+            handle_jira_request(event.kind.clone(), Bytes::from(event.payload.clone()), state_shared).await
+        },
+        EventSource::Teams => {
+            let request = serde_json::from_slice::<teams::Request>(&event.payload)
+                .context("Failed to deserialize payload")?;
+
+            handle_teams_request(request, state_shared).await
+        },
+    }
+}
@@ -0,0 +1,16 @@
+use envconfig::Envconfig;
+
+#[derive(Envconfig, Clone)]
+pub struct Config {
+    /// Periodically looks for Teams messages and Jira comments missed by webhooks.
+    #[envconfig(from = "RECONCILE_ENABLED", default = "false")]
+    pub(crate) enabled: bool,
+    #[envconfig(from = "RECONCILE_INTERVAL_MINS", default = "60")]
+    pub(crate) interval_mins: u64,
+    /// Messages and issues modified within this period are checked.
+    #[envconfig(from = "RECONCILE_LOOKBACK_HOURS", default = "24")]
+    pub(crate) lookback_hours: u32,
+    /// Scheduled runs only report differences.
+    #[envconfig(from = "RECONCILE_DRY_RUN", default = "false")]
+    pub(crate) dry_run: bool,
+}
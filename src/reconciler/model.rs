@@ -0,0 +1,283 @@
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use serde_json::json;
+use tokio::{sync::Mutex, time::sleep};
+use tracing::{error, info, warn};
+
+use crate::{
+    jira_api::{comment_v3::JiraCommentV3, issue::Issue, model::parse_date},
+    logging::model::CorrelationId,
+    ms_graph_api::message::MsGraphMessage,
+    routes::model::Route,
+    server::{handlers::{helpers::extract_message_id_from_url, jira::is_service_user}, AppStateShared},
+    store::event::EventSource,
+};
+
+use super::cfg::Config;
+
+/// Finds Teams messages and Jira comments missed by webhooks (e.g. while the subscription was lapsed
+/// or the service was down) and queues them to the outbox as if the webhooks arrived.
+pub struct Reconciler {
+    pub(crate) config: Config,
+    running: Mutex<()>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Report {
+    pub(crate) dry_run: bool,
+    pub(crate) differences: Vec<Difference>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Difference {
+    pub(crate) kind: DifferenceKind,
+    pub(crate) project_key: String,
+    /// Teams message ID for issues and comments, Jira comment ID for replies.
+    pub(crate) id: String,
+    #[serde(skip)]
+    event: (EventSource, String, Vec<u8>),
+}
+
+/// What is missing or outdated on the other side.
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum DifferenceKind {
+    /// Teams message without issue.
+    MissingIssue,
+    /// Teams reply without comment.
+    MissingComment,
+    /// Teams reply edited after the comment was synced.
+    OutdatedComment,
+    /// Jira comment without reply.
+    MissingReply,
+    /// Jira comment edited after the reply was synced.
+    OutdatedReply,
+}
+
+impl Reconciler {
+    pub fn new(config: Config) -> Self {
+        Self { config, running: Mutex::new(()) }
+    }
+
+    /// Reconciles every `RECONCILE_INTERVAL_MINS` minutes until the process stops.
+    pub async fn run(state_shared: AppStateShared) -> Result<()> {
+        let config = &state_shared.reconciler.config;
+
+        if !config.enabled {
+            return Ok(());
+        }
+
+        let interval = std::time::Duration::from_secs(config.interval_mins.max(1) * 60);
+
+        loop {
+            sleep(interval).await;
+
+            match state_shared.reconciler.reconcile(&state_shared, config.dry_run).await {
+                Ok(report) => info!("Reconciliation found {} differences", report.differences.len()),
+                Err(e) => error!("Failed to reconcile: {:#}", e),
+            }
+        }
+    }
+
+    /// Compares messages and issues modified within `RECONCILE_LOOKBACK_HOURS`. Differences are queued
+    /// to the outbox to be synced, in dry run they are only reported.
+    pub(crate) async fn reconcile(&self, state_shared: &AppStateShared, dry_run: bool) -> Result<Report> {
+        let _running = self.running.try_lock().map_err(|_| anyhow!("Reconciliation is already running"))?;
+
+        let since = Utc::now() - Duration::hours(self.config.lookback_hours.into());
+        let token = state_shared.microsoft.get_token().await?;
+        let mut differences = Vec::new();
+
+        for route in state_shared.routes.iter() {
+            RouteCheck { state_shared, route, token: &token, since, lookback_hours: self.config.lookback_hours }
+                .run(&mut differences)
+                .await
+                .with_context(|| format!("Failed to reconcile project {}", route.project_key))?;
+        }
+
+        for difference in &differences {
+            let correlation_id = CorrelationId::new();
+
+            warn!(
+                correlation_id = %correlation_id.0,
+                "Reconciliation: {:?} in {}: {}", difference.kind, difference.project_key, difference.id,
+            );
+
+            if !dry_run {
+                let (source, kind, payload) = &difference.event;
+                state_shared.outbox.push(state_shared, *source, kind, payload, &correlation_id).await?;
+            }
+        }
+
+        Ok(Report { dry_run, differences })
+    }
+}
+
+struct RouteCheck<'a> {
+    state_shared: &'a AppStateShared,
+    route: &'a Route,
+    token: &'a str,
+    since: DateTime<Utc>,
+    lookback_hours: u32,
+}
+
+impl RouteCheck<'_> {
+    async fn run(&self, differences: &mut Vec<Difference>) -> Result<()> {
+        let state_shared = self.state_shared;
+
+        // Threads to check replies and comments of, by root message ID
+        let mut threads = BTreeMap::new();
+
+        for issue in Issue::search_updated(state_shared, self.route, self.lookback_hours).await? {
+            if let Some(message_id) = issue.get_teams_link(&self.route.link_field_name).and_then(extract_message_id_from_url) {
+                threads.insert(message_id, issue);
+            }
+        }
+
+        let messages = MsGraphMessage::list_modified(&state_shared.microsoft.client, self.token, self.route, self.since).await?;
+
+        for message in messages {
+            if threads.contains_key(&message.id) || !self.is_synced_kind(&message).await? {
+                continue;
+            }
+
+            let issue = Issue::find(state_shared.clone(), self.route, message.web_url.as_deref().unwrap_or_default(), &message.id).await?;
+
+            match issue {
+                Some(issue) => {
+                    threads.insert(message.id, issue);
+                },
+                None => differences.push(self.teams_difference(DifferenceKind::MissingIssue, &message.id, None)),
+            }
+        }
+
+        for (message_id, issue) in &threads {
+            self.check_thread(message_id, issue, differences).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn check_thread(&self, message_id: &str, issue: &Issue, differences: &mut Vec<Difference>) -> Result<()> {
+        let state_shared = self.state_shared;
+        let store = &state_shared.store;
+
+        let comments = JiraCommentV3::list(&state_shared.jira, &issue.get_id()).await?;
+        let synced_replies: HashSet<String> = comments.iter().filter_map(|c| c.get_reply_id()).collect();
+
+        let replies = MsGraphMessage::list_replies(&state_shared.microsoft.client, self.token, self.route, message_id).await?;
+
+        for reply in replies {
+            let Some(modified) = reply.last_modified_date_time.filter(|d| *d >= self.since) else {
+                continue;
+            };
+
+            if !self.is_synced_kind(&reply).await? {
+                continue;
+            }
+
+            let kind = if !synced_replies.contains(&reply.id) && store.get_comment_by_reply_id(&self.route.channel_id, &reply.id).await?.is_none() {
+                DifferenceKind::MissingComment
+            } else if store.get_comment_synced_at(&self.route.channel_id, &reply.id).await?.is_some_and(|t| t < modified.timestamp()) {
+                DifferenceKind::OutdatedComment
+            } else {
+                continue;
+            };
+
+            differences.push(self.teams_difference(kind, message_id, Some(&reply.id)));
+        }
+
+        for comment in comments {
+            let Some(updated) = comment.updated.as_deref().and_then(parse_date).filter(|d| *d >= self.since) else {
+                continue;
+            };
+
+            // Comments by the service user are copies of Teams replies
+            let Some(author) = comment.update_author.as_ref().or(comment.author.as_ref()) else {
+                continue;
+            };
+
+            if is_service_user(state_shared, &author.account_id).await? {
+                continue;
+            }
+
+            let reply_id = match store.get_comment_by_comment_id(&comment.id).await? {
+                Some(link) => Some(link.reply_id),
+                None => comment.get_reply_id(),
+            };
+
+            let (kind, webhook_event) = match reply_id {
+                None => (DifferenceKind::MissingReply, "comment_created"),
+                Some(reply_id) if store.get_comment_synced_at(&self.route.channel_id, &reply_id).await?.is_some_and(|t| t < updated.timestamp()) => {
+                    (DifferenceKind::OutdatedReply, "comment_updated")
+                },
+                Some(_) => continue,
+            };
+
+            let payload = json!({
+                "webhookEvent": webhook_event,
+                "comment": {
+                    "id": comment.id,
+                    "body": comment.body,
+                    "updateAuthor": { "accountId": author.account_id },
+                },
+                "issue": { "id": issue.get_id() },
+            });
+
+            differences.push(Difference {
+                kind,
+                project_key: self.route.project_key.clone(),
+                id: comment.id.clone(),
+                event: (EventSource::Jira, webhook_event.to_string(), payload.to_string().into_bytes()),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Messages handled by the Teams webhook: not deleted, posted by users other than the service user.
+    async fn is_synced_kind(&self, message: &MsGraphMessage) -> Result<bool> {
+        let Some(user) = message.from.user.as_ref().filter(|_| message.is_user_message() && message.deleted_date_time.is_none()) else {
+            return Ok(false);
+        };
+
+        let user = self.state_shared.microsoft.get_user(self.token, user.id).await?;
+
+        Ok(user.mail != self.state_shared.microsoft.config.teams_user)
+    }
+
+    /// Difference synced by Graph change notification of the message or the reply.
+    fn teams_difference(&self, kind: DifferenceKind, message_id: &str, reply_id: Option<&str>) -> Difference {
+        let mut resource = format!("teams('{}')/channels('{}')/messages('{}')", self.route.group_id, self.route.channel_id, message_id);
+
+        if let Some(reply_id) = reply_id {
+            resource.push_str(&format!("/replies('{}')", reply_id));
+        }
+
+        let change_type = match kind {
+            DifferenceKind::OutdatedComment => "updated",
+            _ => "created",
+        };
+
+        let payload = json!({
+            "value": [{
+                "changeType": change_type,
+                "clientState": "",
+                "resource": resource,
+                "subscriptionId": "",
+            }]
+        });
+
+        Difference {
+            kind,
+            project_key: self.route.project_key.clone(),
+            id: reply_id.unwrap_or(message_id).to_string(),
+            event: (EventSource::Teams, "notification".to_string(), payload.to_string().into_bytes()),
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use std::str::FromStr;
+
+use envconfig::Envconfig;
+
+use super::model::Route;
+
+#[derive(Envconfig, Clone)]
+pub struct Config {
+    /// JSON array of routes, see [`Route`]. When empty, a single route is built
+    /// from `TEAMS_GROUP_ID`/`TEAMS_CHANNEL_ID` and `JIRA_PROJECT_KEY` settings.
+    #[envconfig(from = "ROUTES", default = "")]
+    pub(crate) routes: RouteList,
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RouteList(pub(crate) Vec<Route>);
+
+impl FromStr for RouteList {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Ok(Self::default());
+        }
+
+        serde_json::from_str(s).map(Self)
+    }
+}
@@ -0,0 +1,143 @@
+use serde::Deserialize;
+
+use crate::{
+    i18n::model::Catalog,
+    jira_api::{cfg::{Config as JiraConfig, StatusList, StatusTemplates}, issue::Issue},
+    ms_graph_api::cfg::Config as MsGraphApiConfig,
+};
+
+use super::cfg::Config;
+
+const DEFAULT_ISSUE_TYPE: &str = "Task";
+
+/// Link between MS Teams channel and Jira project.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Route {
+    pub(crate) group_id: String,
+    pub(crate) channel_id: String,
+    pub(crate) project_key: String,
+    #[serde(default = "default_issue_type")]
+    pub(crate) issue_type: String,
+    pub(crate) link_field_name: String,
+    pub(crate) link_field_jql_name: String,
+    /// Overrides `JIRA_FINAL_STATUSES` for the project.
+    #[serde(default)]
+    pub(crate) final_statuses: Option<StatusList>,
+    /// Added to `JIRA_STATUS_TEMPLATES` for the project.
+    #[serde(default)]
+    pub(crate) status_templates: StatusTemplates,
+    /// Overrides `JIRA_REOPEN_STATUS` for the project.
+    #[serde(default)]
+    pub(crate) reopen_status: Option<String>,
+}
+
+/// Routing table of the deployment.
+pub struct Routes {
+    routes: Vec<Route>,
+}
+
+fn default_issue_type() -> String {
+    DEFAULT_ISSUE_TYPE.to_string()
+}
+
+impl Route {
+    /// Graph API resource with messages of the channel.
+    pub(crate) fn messages_resource(&self) -> String {
+        format!("/teams/{}/channels/{}/messages", self.group_id, self.channel_id)
+    }
+
+    fn matches_channel(&self, group_id: &str, channel_id: &str) -> bool {
+        self.group_id.eq_ignore_ascii_case(group_id) && self.channel_id.eq_ignore_ascii_case(channel_id)
+    }
+
+    pub(crate) fn is_final_status(&self, jira_config: &JiraConfig, status: &str) -> bool {
+        self.final_statuses
+            .as_ref()
+            .unwrap_or(&jira_config.final_statuses)
+            .contains(status)
+    }
+
+    /// Status to reopen closed issues with, `None` if reopening is disabled.
+    pub(crate) fn reopen_status<'a>(&'a self, jira_config: &'a JiraConfig) -> Option<&'a str> {
+        if !jira_config.reopen_enabled {
+            return None;
+        }
+
+        Some(self.reopen_status.as_deref().unwrap_or(&jira_config.reopen_status).trim())
+            .filter(|s| !s.is_empty())
+    }
+
+    /// Notification template for the status: project template, common template, catalog template
+    /// of the status, then the default one for final or other statuses.
+    pub(crate) fn status_template<'a>(&'a self, jira_config: &'a JiraConfig, catalog: &'a Catalog, locale: Option<&str>, status: &str) -> &'a str {
+        let (default_template, default_key) = if self.is_final_status(jira_config, status) {
+            (&jira_config.final_status_template, "final_status")
+        } else {
+            (&jira_config.status_template, "status_changed")
+        };
+
+        self.status_templates
+            .get(status)
+            .or_else(|| jira_config.status_templates.get(status))
+            .or_else(|| catalog.find(locale, &format!("status.{status}")))
+            .or_else(|| Some(default_template.as_str()).filter(|t| !t.is_empty()))
+            .unwrap_or_else(|| catalog.get(locale, default_key))
+    }
+
+    /// Checks whether Teams message URL points to this route's channel.
+    fn matches_url(&self, url: &str) -> bool {
+        let encoded_channel_id = self.channel_id.replace(':', "%3A").replace('@', "%40");
+        url.contains(&self.channel_id) || url.contains(&encoded_channel_id)
+    }
+}
+
+impl Routes {
+    pub fn new(config: Config, ms_graph_api_config: &MsGraphApiConfig, jira_config: &JiraConfig) -> Self {
+        let mut routes = config.routes.0;
+
+        if routes.is_empty() {
+            routes.push(Route {
+                group_id: ms_graph_api_config.group_id.clone(),
+                channel_id: ms_graph_api_config.channel_id.clone(),
+                project_key: jira_config.project_key.clone(),
+                issue_type: default_issue_type(),
+                link_field_name: jira_config.msteams_link_field_name.clone(),
+                link_field_jql_name: jira_config.msteams_link_field_jql_name.clone(),
+                final_statuses: None,
+                status_templates: StatusTemplates::default(),
+                reopen_status: None,
+            });
+        }
+
+        Self { routes }
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Route> {
+        self.routes.iter()
+    }
+
+    pub(crate) fn find_by_channel(&self, group_id: &str, channel_id: &str) -> Option<&Route> {
+        self.routes
+            .iter()
+            .find(|r| r.matches_channel(group_id, channel_id))
+    }
+
+    /// Finds route of Jira issue and returns it with the link to Teams message.
+    pub(crate) fn find_by_issue(&self, issue: &Issue) -> Option<(&Route, String)> {
+        let project_key = issue.get_project_key()?;
+
+        let candidates: Vec<(&Route, String)> = self.routes
+            .iter()
+            .filter(|r| r.project_key.eq_ignore_ascii_case(&project_key))
+            .filter_map(|r| issue.get_teams_link(&r.link_field_name).map(|link| (r, link)))
+            .collect();
+
+        if candidates.len() == 1 {
+            return candidates.into_iter().next();
+        }
+
+        candidates
+            .into_iter()
+            .find(|(r, link)| r.matches_url(link))
+    }
+}
@@ -0,0 +1,15 @@
+use envconfig::Envconfig;
+
+#[derive(Envconfig, Clone)]
+pub struct Config {
+    /// Closes issues left in `JIRA_CONFIRM_STATUSES` without a reply in Teams.
+    #[envconfig(from = "AUTO_CLOSE_ENABLED", default = "false")]
+    pub(crate) enabled: bool,
+    #[envconfig(from = "AUTO_CLOSE_DAYS", default = "7")]
+    pub(crate) days: u32,
+    /// Reminder is posted this many hours before closure, 0 for no reminder.
+    #[envconfig(from = "AUTO_CLOSE_REMINDER_HOURS", default = "24")]
+    pub(crate) reminder_hours: u32,
+    #[envconfig(from = "AUTO_CLOSE_POLL_INTERVAL_SECS", default = "60")]
+    pub(crate) poll_interval_secs: u64,
+}
@@ -0,0 +1,152 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use chrono_tz::Europe::Moscow;
+use tokio::time::{sleep, Duration};
+use tracing::{error, info, warn};
+
+use crate::{
+    jira_api::{issue::Issue, model::is_not_found, transition::JiraTransition},
+    routes::model::Route,
+    server::AppStateShared,
+    store::schedule::Closure,
+    utils::fill_html_template,
+};
+
+use super::cfg::Config;
+
+const HOUR_SECS: i64 = 60 * 60;
+const DAY_SECS: i64 = 24 * HOUR_SECS;
+
+/// Closes issues waiting for confirmation of the fix when users don't reply in time.
+/// The schedule is kept in the store, so it survives restarts.
+pub struct Scheduler {
+    pub(crate) config: Config,
+}
+
+impl Scheduler {
+    pub fn new(config: Config) -> Self {
+        Self { config }
+    }
+
+    /// Starts the waiting period when the issue gets one of `JIRA_CONFIRM_STATUSES`, cancels it on other statuses.
+    /// Returns whether the closure is scheduled.
+    pub(crate) async fn track(&self, state_shared: &AppStateShared, issue_id: &str, route: &Route, message_id: &str, status: &str) -> Result<bool> {
+        if !self.config.enabled || !state_shared.jira.config.confirm_statuses.contains(status) {
+            state_shared.store.cancel_closure(issue_id).await?;
+            return Ok(false);
+        }
+
+        let close_at = Utc::now().timestamp() + i64::from(self.config.days) * DAY_SECS;
+        let remind_at = Some(close_at - i64::from(self.config.reminder_hours) * HOUR_SECS)
+            .filter(|_| self.config.reminder_hours > 0);
+
+        state_shared.store.schedule_closure(issue_id, &route.channel_id, message_id, remind_at, close_at).await?;
+
+        Ok(true)
+    }
+
+    /// Posts due reminders and closes due issues until the process stops.
+    pub async fn run(state_shared: AppStateShared) -> Result<()> {
+        let config = &state_shared.scheduler.config;
+
+        if !config.enabled {
+            return Ok(());
+        }
+
+        let poll_interval = Duration::from_secs(config.poll_interval_secs);
+
+        loop {
+            if let Err(e) = tick(&state_shared).await {
+                error!("Failed to process scheduled closures: {:#}", e);
+            }
+
+            sleep(poll_interval).await;
+        }
+    }
+}
+
+async fn tick(state_shared: &AppStateShared) -> Result<()> {
+    let now = Utc::now().timestamp();
+
+    for closure in state_shared.store.list_due_reminders(now).await? {
+        if let Err(e) = remind(state_shared, &closure).await {
+            warn!("Failed to remind about closure of issue {}: {:#}", closure.issue_id, e);
+        }
+    }
+
+    for closure in state_shared.store.list_due_closures(now).await? {
+        if let Err(e) = close(state_shared, &closure).await {
+            warn!("Failed to close issue {}: {:#}", closure.issue_id, e);
+        }
+    }
+
+    Ok(())
+}
+
+async fn remind(state_shared: &AppStateShared, closure: &Closure) -> Result<()> {
+    let Some(issue) = awaiting_issue(state_shared, closure).await? else {
+        return Ok(());
+    };
+
+    let Some((route, _)) = state_shared.routes.find_by_issue(&issue) else {
+        return state_shared.store.cancel_closure(&closure.issue_id).await;
+    };
+
+    let locale = state_shared.store.get_issue_locale(&route.channel_id, &closure.message_id).await?;
+    let key = issue.get_key();
+    let url = format!("{}/browse/{}", state_shared.jira.config.base_url, key);
+    let date = DateTime::from_timestamp(closure.close_at, 0)
+        .unwrap_or_default()
+        .with_timezone(&Moscow)
+        .format("%d.%m.%Y %H:%M")
+        .to_string();
+
+    let notice = fill_html_template(
+        state_shared.i18n.get(locale.as_deref(), "auto_close_reminder"),
+        &[("key", &key), ("url", &url), ("date", &date)],
+    );
+
+    issue
+        .notify(state_shared, route, &closure.message_id, locale.as_deref(), Some(&notice), None)
+        .await
+        .context("Failed to post reminder")?;
+
+    state_shared.store.complete_reminder(&closure.issue_id).await
+}
+
+async fn close(state_shared: &AppStateShared, closure: &Closure) -> Result<()> {
+    let Some(issue) = awaiting_issue(state_shared, closure).await? else {
+        return Ok(());
+    };
+
+    let status = &state_shared.jira.config.confirmed_status;
+
+    // Status webhook from Jira notifies the thread
+    if JiraTransition::to_status(&state_shared.jira, &closure.issue_id, status).await? {
+        info!("Issue {} closed automatically", issue.get_key());
+    } else {
+        warn!("Issue {} can't be moved to {}, closure is cancelled", issue.get_key(), status);
+    }
+
+    state_shared.store.cancel_closure(&closure.issue_id).await
+}
+
+/// Issue of the closure if it's still waiting for confirmation, otherwise the closure is cancelled.
+async fn awaiting_issue(state_shared: &AppStateShared, closure: &Closure) -> Result<Option<Issue>> {
+    let issue = match Issue::get_issue(&state_shared.jira, &closure.issue_id).await {
+        Ok(issue) => issue,
+        Err(e) if is_not_found(&e) => {
+            state_shared.store.cancel_closure(&closure.issue_id).await?;
+            return Ok(None);
+        },
+        Err(e) => return Err(e),
+    };
+
+    if issue.get_status().is_some_and(|s| state_shared.jira.config.confirm_statuses.contains(&s)) {
+        return Ok(Some(issue));
+    }
+
+    state_shared.store.cancel_closure(&closure.issue_id).await?;
+
+    Ok(None)
+}
@@ -6,4 +6,9 @@ pub struct Config {
     pub(crate) addr: String,
     #[envconfig(from = "SHUTDOWN_TIMEOUT", default = "60")]
     pub shutdown_timeout: u64,
+    #[envconfig(from = "ADMIN_TOKEN", default = "")]
+    pub(crate) admin_token: String,
+    /// Bearer token of `/metrics`, metrics are public if empty.
+    #[envconfig(from = "METRICS_TOKEN", default = "")]
+    pub(crate) metrics_token: String,
 }
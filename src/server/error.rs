@@ -74,21 +74,21 @@ impl Error {
     //     Self::new(StatusCode::NOT_ACCEPTABLE, Some(err))
     // }
 
-    // pub(crate) fn c404<E: Display>(err: E) -> Self {
-    //     Self::new(StatusCode::NOT_FOUND, Some(err))
-    // }
+    pub(crate) fn c404<E: Display>(err: E) -> Self {
+        Self::new(StatusCode::NOT_FOUND, Some(err))
+    }
 
-    // pub(crate) fn c401() -> Self {
-    //     Self::new(StatusCode::UNAUTHORIZED, Some("unauthorized"))
-    // }
+    pub(crate) fn c401() -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, Some("unauthorized"))
+    }
 
     // pub(crate) fn c403() -> Self {
     //     Self::new(StatusCode::FORBIDDEN, Some("forbidden"))
     // }
 
-    // pub(crate) fn c400<E: Display>(err: E) -> Self {
-    //     Self::new(StatusCode::BAD_REQUEST, Some(err))
-    // }
+    pub(crate) fn c400<E: Display>(err: E) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, Some(err))
+    }
 
     // pub(crate) fn c410() -> Self {
     //     Self::new(StatusCode::GONE, Some("disabled"))
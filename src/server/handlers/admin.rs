@@ -0,0 +1,176 @@
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{Response, Result as ApiResult},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use tracing::Instrument;
+
+use crate::{
+    metrics::model::record_webhook,
+    ms_graph_api::subscription::SubscriptionStatus,
+    outbox::model::{dispatch, event_span},
+    reconciler::model::Report,
+    server::{error::Error as ApiError, AppStateShared},
+    store::event::{Event, EventStatus},
+};
+
+const DEFAULT_EVENTS_LIMIT: u32 = 50;
+
+#[derive(Deserialize)]
+pub(crate) struct EventsQuery {
+    pub(crate) status: Option<String>,
+    pub(crate) limit: Option<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) struct ReconcileQuery {
+    #[serde(default)]
+    pub(crate) dry_run: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct EventResponse {
+    pub(crate) id: i64,
+    pub(crate) source: &'static str,
+    pub(crate) kind: String,
+    pub(crate) status: &'static str,
+    pub(crate) attempts: u32,
+    pub(crate) error: Option<String>,
+    pub(crate) payload: Value,
+    pub(crate) created_at: String,
+    pub(crate) updated_at: String,
+    pub(crate) correlation_id: Option<String>,
+}
+
+impl From<Event> for EventResponse {
+    fn from(event: Event) -> Self {
+        let payload = serde_json::from_slice::<Value>(&event.payload)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&event.payload).into_owned()));
+
+        Self {
+            id: event.id,
+            source: event.source.as_str(),
+            kind: event.kind,
+            status: event.status.as_str(),
+            attempts: event.attempts,
+            error: event.last_error,
+            payload,
+            created_at: event.created_at,
+            updated_at: event.updated_at,
+            correlation_id: event.correlation_id,
+        }
+    }
+}
+
+/// Allows only requests with `Authorization: Bearer <ADMIN_TOKEN>` header.
+pub(crate) async fn auth(
+    State(admin_token): State<String>,
+    headers: HeaderMap,
+    request: Request,
+    next: Next,
+) -> ApiResult<Response, ApiError> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .unwrap_or_default();
+
+    // Digests are compared in constant time, so neither the token nor its length leaks through timing
+    let valid = bool::from(Sha256::digest(token).ct_eq(&Sha256::digest(&admin_token)));
+
+    if admin_token.is_empty() || !valid {
+        return Err(ApiError::c401());
+    }
+
+    Ok(next.run(request).await)
+}
+
+pub(crate) async fn list_events(
+    State(state_shared): State<AppStateShared>,
+    Query(query): Query<EventsQuery>,
+) -> ApiResult<Json<Vec<EventResponse>>, ApiError> {
+    let status = query.status
+        .as_deref()
+        .map(EventStatus::parse)
+        .transpose()
+        .map_err(ApiError::c400)?;
+
+    let events = state_shared.store
+        .list_failed_events(status, query.limit.unwrap_or(DEFAULT_EVENTS_LIMIT))
+        .await
+        .map_err(ApiError::c500)?;
+
+    Ok(Json(events.into_iter().map(EventResponse::from).collect()))
+}
+
+/// Processes the event right away. On success the event is removed from the queue,
+/// otherwise it's left in dead-letter state with the new error.
+pub(crate) async fn replay_event(
+    State(state_shared): State<AppStateShared>,
+    Path(id): Path<i64>,
+) -> ApiResult<StatusCode, ApiError> {
+    let event = state_shared.store
+        .claim_event(id)
+        .await
+        .map_err(ApiError::c500)?
+        .ok_or_else(|| ApiError::c404("Event not found or is being processed"))?;
+
+    match dispatch(state_shared.clone(), &event).instrument(event_span(&event)).await {
+        Ok(()) => {
+            record_webhook(event.source.as_str(), &event.kind, "processed");
+            state_shared.store.complete_event(id).await.map_err(ApiError::c500)?;
+            Ok(StatusCode::OK)
+        },
+        Err(e) => {
+            record_webhook(event.source.as_str(), &event.kind, "dead");
+            state_shared.store
+                .fail_event(id, &format!("{:#}", e), None)
+                .await
+                .map_err(ApiError::c500)?;
+            Err(ApiError::c500(e))
+        },
+    }
+}
+
+pub(crate) async fn discard_event(
+    State(state_shared): State<AppStateShared>,
+    Path(id): Path<i64>,
+) -> ApiResult<StatusCode, ApiError> {
+    let discarded = state_shared.store
+        .discard_event(id)
+        .await
+        .map_err(ApiError::c500)?;
+
+    if discarded {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::c404("Event not found or is being processed"))
+    }
+}
+
+/// Runs reconciliation right away, `?dry_run=true` only reports differences.
+pub(crate) async fn reconcile(
+    State(state_shared): State<AppStateShared>,
+    Query(query): Query<ReconcileQuery>,
+) -> ApiResult<Json<Report>, ApiError> {
+    let report = state_shared.reconciler
+        .reconcile(&state_shared, query.dry_run)
+        .await
+        .map_err(ApiError::c500)?;
+
+    Ok(Json(report))
+}
+
+pub(crate) async fn list_subscriptions(
+    State(state_shared): State<AppStateShared>,
+) -> Json<Vec<SubscriptionStatus>> {
+    Json(state_shared.microsoft.subscriptions_status().await)
+}
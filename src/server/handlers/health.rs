@@ -0,0 +1,85 @@
+use std::time::Instant;
+
+use anyhow::{anyhow, Result};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    Json,
+};
+use chrono::Utc;
+use serde::Serialize;
+use tokio::time::{timeout, Duration};
+
+use crate::server::AppStateShared;
+
+/// Longest wait for a check making a request.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Readiness {
+    pub(crate) ready: bool,
+    pub(crate) checks: Vec<Check>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Check {
+    pub(crate) name: String,
+    pub(crate) ok: bool,
+    pub(crate) error: Option<String>,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, result: Result<()>) -> Self {
+        Self {
+            name: name.into(),
+            ok: result.is_ok(),
+            error: result.err().map(|e| format!("{:#}", e)),
+        }
+    }
+}
+
+/// The process is up.
+pub(crate) async fn healthz() -> StatusCode {
+    StatusCode::OK
+}
+
+/// Whether messages can be synced: both tokens are valid, subscriptions are active and Jira accepts the credentials.
+/// Responds with 503 and the failed checks otherwise.
+pub(crate) async fn readyz(State(state_shared): State<AppStateShared>) -> (StatusCode, Json<Readiness>) {
+    let microsoft = &state_shared.microsoft;
+    let status = microsoft.state_status().await;
+
+    let application_token = match status.token_expires_at {
+        Some(expires_at) if expires_at > Instant::now() => Ok(()),
+        // The token is renewed on demand, so the expired one doesn't mean an outage yet
+        _ => timeout(CHECK_TIMEOUT, microsoft.get_token())
+            .await
+            .unwrap_or_else(|_| Err(anyhow!("Token renewal timed out")))
+            .map(|_| ()),
+    };
+
+    let mut checks = vec![
+        Check::new("applicationToken", application_token),
+        // Missing delegated token is the most common outage: replies can't be posted until the Teams user authenticates
+        Check::new("delegatedToken", microsoft.granted_token.read().await.get().map(|_| ())),
+    ];
+
+    for subscription in status.subscriptions {
+        let result = match subscription.expires_at {
+            _ if subscription.id.is_nil() => Err(anyhow!("Subscription isn't created")),
+            Some(expires_at) if expires_at > Utc::now() => Ok(()),
+            _ => Err(anyhow!("Subscription expired")),
+        };
+
+        checks.push(Check::new(format!("subscription:{}", subscription.project_key), result));
+    }
+
+    checks.push(Check::new("jira", state_shared.jira.get_myself(CHECK_TIMEOUT).await.map(|_| ())));
+
+    let ready = checks.iter().all(|c| c.ok);
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+
+    (status, Json(Readiness { ready, checks }))
+}
@@ -1,6 +1,5 @@
 use regex::Regex;
 use serde::Deserialize;
-use tokio::{fs::OpenOptions, io::AsyncWriteExt};
 
 
 #[derive(Deserialize)]
@@ -9,15 +8,11 @@ pub(crate) struct ValidationTokenQuery {
     pub(crate) validation_token: Option<String>,
 }
 
-pub(crate) async fn log_to_file(handler: &str, payload: &str) {
-    if let Ok(mut file) = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open("test.txt")
-        .await {
-            let data = format!("{}\t{}\t{}\n", chrono::Local::now().format("%d.%m.%Y %H:%M:%S"), handler, payload);
-            let _ = file.write_all(data.as_bytes()).await;
-    };
+/// ID of the root message from Teams message link like `.../<message ID>?groupId=...`.
+pub(crate) fn extract_message_id_from_url(url: String) -> Option<String> {
+    let start_pos = url.rfind('/')? + 1;
+    let end_pos = url[start_pos..].find('?')? + start_pos;
+    Some(url[start_pos..end_pos].to_string())
 }
 
 pub(crate) fn get_message_id_and_reply_id(resource: &str) -> (Option<String>, Option<String>) {
@@ -35,6 +30,21 @@ pub(crate) fn get_message_id_and_reply_id(resource: &str) -> (Option<String>, Op
     (message_id, reply_id)
 }
 
+/// Extracts team and channel IDs from resource like `teams('..')/channels('..')/messages('..')`.
+pub(crate) fn get_group_id_and_channel_id(resource: &str) -> Option<(String, String)> {
+    let mut group_id = None;
+    let mut channel_id = None;
+    for part in resource.split("/") {
+        if part.starts_with("teams") {
+            group_id = get_id(part);
+        } else if part.starts_with("channels") {
+            channel_id = get_id(part);
+        };
+    }
+
+    group_id.zip(channel_id)
+}
+
 fn get_id(text: &str) -> Option<String> {
     let re = Regex::new(r"\w+\('([^']*)'\)").unwrap();
 
@@ -1,6 +1,6 @@
 use anyhow::{ensure, Context, Result};
 use axum::body::Bytes;
-use axum::extract::State;
+use axum::extract::{Extension, State};
 use axum::http::{header::HeaderMap, HeaderName, StatusCode};
 use axum::response::Result as ApiResult;
 use chrono_tz::Europe::Moscow;
@@ -8,22 +8,36 @@ use hmac::{Hmac, KeyInit, Mac};
 use serde::Deserialize;
 use serde_json::Value;
 use sha2::Sha256;
+use tracing::{debug, error};
 type HmacSha256 = Hmac<Sha256>;
 
+use crate::jira_api::cfg::DeleteMode;
 use crate::jira_api::comment::JiraComment;
 use crate::jira_api::comment_v3::JiraCommentV3;
 use crate::jira_api::issue::Issue;
-use crate::ms_graph_api::model::MSGraphAPI;
+use crate::jira_api::issue_v3::JiraIssueV3;
+use crate::jira_api::render::TeamsReply;
+use crate::ms_graph_api::message::ReplyParts;
+use crate::jira_api::model::JiraUser;
+use crate::logging::model::CorrelationId;
+use crate::metrics::model::record_synced;
 use crate::server::error::Error as ApiError;
 use crate::server::AppStateShared;
+use crate::store::event::EventSource;
+use crate::store::model::CommentLink;
+use crate::utils::{fill_html_template, html_escape};
 
-use super::helpers::log_to_file;
+use super::helpers::extract_message_id_from_url;
+
+/// Kind of the bot reply with the issue summary and description.
+const DETAILS_REPLY_KIND: &str = "details";
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct IssueRequest {
     pub(crate) issue: Issue,
     pub(crate) changelog: ChangeLog,
+    pub(crate) user: Option<JiraUser>,
 }
 
 #[derive(Deserialize)]
@@ -42,9 +56,17 @@ pub(crate) struct IssueId {
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct ChangeLog {
+    pub(crate) id: Option<String>,
     pub(crate) items: Vec<ChangeLogItem>,
 }
 
+impl ChangeLog {
+    /// Bot reply kind of the notice about the change, `None` if the changelog has no ID.
+    fn notice_kind(&self, field: &str) -> Option<String> {
+        self.id.as_ref().map(|id| format!("notice:{id}:{field}"))
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct ChangeLogItem {
@@ -60,13 +82,14 @@ struct Signature {
 
 pub(crate) async fn handler(
     State(state_shared): State<AppStateShared>,
+    Extension(correlation_id): Extension<CorrelationId>,
     headers: HeaderMap,
     payload: axum::body::Bytes,
 )-> ApiResult<StatusCode, ApiError> {
-    match parse_handler(state_shared, headers, payload).await {
+    match parse_handler(state_shared, &correlation_id, headers, payload).await {
         Ok(()) => Ok(StatusCode::OK),
         Err(e) => {
-            log_to_file("jira", &e.to_string()).await;
+            error!("Failed to accept jira request: {:#}", e);
             Err(ApiError::c500(e))
         }
     }
@@ -74,6 +97,7 @@ pub(crate) async fn handler(
 
 async fn parse_handler(
     state_shared: AppStateShared,
+    correlation_id: &CorrelationId,
     headers: HeaderMap,
     payload: axum::body::Bytes,
 ) -> Result<()> {
@@ -93,11 +117,9 @@ async fn parse_handler(
         .unwrap_or_default()
         .to_string();
 
-    tokio::task::spawn(async move { 
-        handle_jira_request(webhook_event, payload, state_shared).await 
-    });
-
-    Ok(())
+    state_shared.outbox
+        .push(&state_shared, EventSource::Jira, &webhook_event, &payload, correlation_id)
+        .await
 }
 
 fn validate_signature(payload: &Bytes, secret: &str, signature: &Signature) -> Result<()> {
@@ -116,10 +138,10 @@ fn validate_signature(payload: &Bytes, secret: &str, signature: &Signature) -> R
     Ok(())
 }
 
-fn extract_message_id_from_url(url: String) -> Option<String> {
-    let start_pos = url.rfind('/')? + 1;
-    let end_pos = url[start_pos..].find('?')? + start_pos;
-    Some(url[start_pos..end_pos].to_string())
+pub(crate) async fn is_service_user(state_shared: &AppStateShared, account_id: &str) -> Result<bool> {
+    let user = state_shared.jira.find_user_by_id(account_id).await.context("Failed to get user")?;
+
+    Ok(user.email_address.is_some_and(|e| e.to_lowercase() == state_shared.jira.config.user.to_lowercase()))
 }
 
 fn get_signature_from_headers(headers: HeaderMap) -> Result<Signature> {
@@ -153,52 +175,154 @@ async fn parse_comment(payload: Bytes, state_shared: AppStateShared) -> Result<(
 
     let issue = Issue::get_issue(&state_shared.jira, &request.issue.id).await.context("Failed to get comment issue by id")?;
 
-    if let Some(message_id) = extract_message_id_from_url(issue.get_teams_link().unwrap_or_default()) {
-        let comment = JiraCommentV3::get(&state_shared.jira, &issue.get_id(), &request.comment.id).await?;
+    let Some((route, link)) = state_shared.routes.find_by_issue(&issue) else {
+        return Ok(());
+    };
 
-        let mut body = comment.body.clone();
+    if let Some(message_id) = extract_message_id_from_url(link) {
+        state_shared.store.set_issue_id(&route.channel_id, &message_id, &issue.get_id()).await?;
 
-        body.replace_media_urls(&state_shared.jira.config.base_url, &comment.rendered_body);
+        let comment = JiraCommentV3::get(&state_shared.jira, &issue.get_id(), &request.comment.id).await?;
 
         let comment_url = format!("https://plnew.atlassian.net/browse/{}?focusedCommentId={}", issue.get_key(), request.comment.id);
 
-        let reply_body = body.to_html(Some(Moscow), &comment_url);
+        let (reply_body, parts) = TeamsReply {
+                route,
+                issue_key: &issue.get_key(),
+                body: &comment.body,
+                rendered_body: &comment.rendered_body,
+                link: &comment_url,
+            }
+            .render(&state_shared, Some(Moscow))
+            .await?;
 
-        if let Some(reply_id) = comment.get_reply_id() {
+        let reply_id = if let Some(reply_id) = comment.get_reply_id() {
             state_shared.microsoft
-                .edit_reply(&message_id, &reply_id, &reply_body)
+                .edit_reply(route, &message_id, &reply_id, &reply_body, &parts)
                 .await
                 .context("Failed to update reply in channel")?;
+            record_synced("reply", false);
+            reply_id
         } else {
             let reply_id = state_shared.microsoft
-                .reply_to_issue(&message_id, &reply_body)
+                .reply_to_issue(route, &message_id, &reply_body, &parts)
                 .await
                 .context("Failed to add reply to the channel")?
                 .id;
             comment.add_reply_id(&state_shared.jira, &reply_id).await?;
-        }
+            record_synced("reply", true);
+            reply_id
+        };
+
+        state_shared.store
+            .set_comment(&CommentLink {
+                channel_id: route.channel_id.clone(),
+                reply_id,
+                message_id,
+                issue_id: issue.get_id(),
+                comment_id: comment.id.clone(),
+            })
+            .await?;
     }
 
     Ok(())
 }
 
-async fn parse_issue(payload: Bytes, graph_api: &MSGraphAPI) -> Result<()> {
+/// Mirrors deletion of Jira comment to the linked Teams reply according to `SYNC_DELETE_MODE`.
+async fn parse_deleted_comment(payload: Bytes, state_shared: AppStateShared) -> Result<()> {
+    let request = serde_json::from_slice::<CommentRequest>(&payload)
+        .context("Failed to deserialize payload")?;
+
+    let delete_mode = state_shared.jira.config.delete_mode;
+
+    if delete_mode == DeleteMode::Off {
+        return Ok(());
+    }
+
+    // Properties of a deleted comment are gone, so the reply is found through the store only
+    let Some(link) = state_shared.store.get_comment_by_comment_id(&request.comment.id).await? else {
+        return Ok(());
+    };
+
+    // Comments by the service user are copies of Teams replies, those are left to their authors
+    if let Some(author) = &request.comment.author
+        && is_service_user(&state_shared, &author.account_id).await?
+    {
+        return state_shared.store.remove_comment(&link.channel_id, &link.reply_id).await;
+    }
+
+    let issue = Issue::get_issue(&state_shared.jira, &request.issue.id).await.context("Failed to get comment issue by id")?;
+
+    if let Some((route, _)) = state_shared.routes.find_by_issue(&issue) {
+        match delete_mode {
+            DeleteMode::Hard => {
+                state_shared.microsoft
+                    .delete_reply(route, &link.message_id, &link.reply_id)
+                    .await
+                    .context("Failed to delete reply in channel")?;
+            },
+            _ => {
+                let reply = state_shared.microsoft
+                    .get_reply(route, &link.message_id, &link.reply_id)
+                    .await
+                    .context("Failed to get reply from channel")?;
+
+                let locale = state_shared.store.get_issue_locale(&link.channel_id, &link.message_id).await?;
+                let notice = state_shared.i18n.get(locale.as_deref(), "deleted_in_jira");
+
+                state_shared.microsoft
+                    .edit_reply(route, &link.message_id, &link.reply_id, &format!("<i>{}</i><br><s>{}</s>", notice, reply.body.content), &ReplyParts::of(&reply))
+                    .await
+                    .context("Failed to update reply in channel")?;
+            },
+        }
+    }
+
+    // The link is kept until the reply is handled, so a failed attempt can be retried
+    state_shared.store.remove_comment(&link.channel_id, &link.reply_id).await
+}
+
+async fn parse_issue(payload: Bytes, state_shared: AppStateShared) -> Result<()> {
     let request = serde_json::from_slice::<IssueRequest>(&payload)
         .context("Failed to deserialize payload")?;
 
-    if let Some(link) = request.issue.get_teams_link() {
+    if let Some((route, link)) = state_shared.routes.find_by_issue(&request.issue) {
+        let Some(message_id) = extract_message_id_from_url(link) else {
+            return Ok(());
+        };
+
+        let config = &state_shared.jira.config;
+        let locale = state_shared.store.get_issue_locale(&route.channel_id, &message_id).await?;
+        let locale = locale.as_deref();
+        let key = request.issue.get_key();
+        let url = format!("{}/browse/{}", config.base_url, key);
+        let status = request.issue.get_status().unwrap_or_default();
+        let assignee = request.issue.get_assignee_name();
+        let days = state_shared.scheduler.config.days.to_string();
+
+        let values = [
+            ("status", status.as_str()),
+            ("assignee", assignee.as_deref().unwrap_or_default()),
+            ("key", key.as_str()),
+            ("url", url.as_str()),
+            ("days", days.as_str()),
+        ];
+
         if request
             .changelog
             .items
             .iter()
             .any(|i| i.field.to_lowercase() == "assignee")
-            && let Some(message_id) = extract_message_id_from_url(link.to_string())
-            && let Some(assignee) = request.issue.get_assignee_name() 
+            && assignee.is_some()
         {
-            let reply_body = format!("Вашей задачей будет заниматься {assignee}");
+            let template = Some(config.assignee_template.as_str())
+                .filter(|t| !t.is_empty())
+                .unwrap_or_else(|| state_shared.i18n.get(locale, "assignee_changed"));
 
-            graph_api
-                .reply_to_issue(&message_id, &reply_body)
+            let reply_body = fill_html_template(template, &values);
+
+            request.issue
+                .notify(&state_shared, route, &message_id, locale, Some(&reply_body), request.changelog.notice_kind("assignee").as_deref())
                 .await
                 .context("Failed to send notification to the channel")?;
         }
@@ -208,38 +332,97 @@ async fn parse_issue(payload: Bytes, graph_api: &MSGraphAPI) -> Result<()> {
             .items
             .iter()
             .any(|i| i.field.to_lowercase() == "status")
-            && let Some(message_id) = extract_message_id_from_url(link.to_string()) 
         {
-            let mut reply_body = format!("Статус задачи изменён на {}", request.issue.get_status().unwrap_or_default());
+            // Scheduled first, so failed notification doesn't leave the issue without closure
+            let scheduled = state_shared.scheduler
+                .track(&state_shared, &request.issue.get_id(), route, &message_id, &status)
+                .await
+                .context("Failed to schedule issue closure")?;
+
+            let template = route.status_template(config, &state_shared.i18n, locale, &status);
+            let mut reply_body = fill_html_template(template, &values);
 
-            if request.issue.get_status().is_some_and(|s| s.to_lowercase() == "Implementation/Test".to_lowercase()) {
-                reply_body.push_str("<br>Ваша задача выполнена. Проверьте и подтвердите, что всё ОК.<br>При отсутствиие ответа эта задача автоматически закроется через 7 дней");
-            } else if request.issue.final_status() {
-                reply_body.push_str("<br>Ваша задача закрыта. Если проблема сохранилась, заведите новую задачу");
+            // Templates mentioning `{days}` announce the closure themselves
+            if scheduled && !template.contains("{days}") {
+                reply_body.push_str("<br>");
+                reply_body.push_str(&fill_html_template(state_shared.i18n.get(locale, "auto_close_notice"), &values));
             }
 
-            graph_api
-                .reply_to_issue(&message_id, &reply_body)
+            request.issue
+                .notify(&state_shared, route, &message_id, locale, Some(&reply_body), request.changelog.notice_kind("status").as_deref())
                 .await
                 .context("Failed to send notification to the channel")?;
         }
+
+        let details_changed = request
+            .changelog
+            .items
+            .iter()
+            .any(|i| ["summary", "description"].contains(&i.field.to_lowercase().as_str()));
+
+        // Service user edits the issue when the Teams message is edited, no need to echo it back
+        let by_service_user = match &request.user {
+            Some(user) if details_changed => is_service_user(&state_shared, &user.account_id).await?,
+            _ => false,
+        };
+
+        if details_changed && !by_service_user {
+            let details = JiraIssueV3::get(&state_shared.jira, &request.issue.get_id())
+                .await
+                .context("Failed to get issue details")?;
+
+            let (description, parts) = match &details.fields.description {
+                Some(description) => {
+                    let rendered_description = details.rendered_fields
+                        .as_ref()
+                        .and_then(|f| f.description.as_deref())
+                        .unwrap_or_default();
+
+                    TeamsReply {
+                            route,
+                            issue_key: &key,
+                            body: description,
+                            rendered_body: rendered_description,
+                            link: &url,
+                        }
+                        .render(&state_shared, Some(Moscow))
+                        .await?
+                },
+                None => (String::new(), ReplyParts::default()),
+            };
+
+            let reply_body = format!(
+                "{}<br><b>{}</b><br>{}",
+                fill_html_template(state_shared.i18n.get(locale, "issue_details_updated"), &values),
+                html_escape(&details.fields.summary),
+                description,
+            );
+
+            state_shared.microsoft
+                .upsert_bot_reply(&state_shared.store, route, &message_id, DETAILS_REPLY_KIND, &reply_body, &parts)
+                .await
+                .context("Failed to send issue details to the channel")?;
+        }
     }
 
     Ok(())
 }
 
-async fn handle_jira_request(webhook_event: String, payload: Bytes, state_shared: AppStateShared) -> anyhow::Result<()> {
+pub(crate) async fn handle_jira_request(webhook_event: String, payload: Bytes, state_shared: AppStateShared) -> anyhow::Result<()> {
     let result = match webhook_event.as_str() {
         "comment_created" | "comment_updated" => { 
                 parse_comment(payload.clone(), state_shared).await.context("Failed to parse comment")
             },
+        "comment_deleted" => { 
+                parse_deleted_comment(payload.clone(), state_shared).await.context("Failed to parse deleted comment")
+            },
         _ => { 
-                parse_issue(payload.clone(), &state_shared.microsoft).await.context("Failed to parse issue")
+                parse_issue(payload.clone(), state_shared).await.context("Failed to parse issue")
             },
     };
 
     if let Err(e) = result {
-        log_to_file("handle jira request", &format!("Got error:\n{:?}\n\nfor payload:\n{}", e, String::from_utf8_lossy(&payload))).await;
+        debug!(payload = %String::from_utf8_lossy(&payload), "Failed to handle {} webhook: {:#}", webhook_event, e);
         return Err(e);
     }
 
@@ -0,0 +1,14 @@
+use axum::{
+    extract::State,
+    http::header,
+    response::{IntoResponse, Result as ApiResult},
+};
+
+use crate::server::{error::Error as ApiError, AppStateShared};
+
+/// Metrics in Prometheus text format.
+pub(crate) async fn handler(State(state_shared): State<AppStateShared>) -> ApiResult<impl IntoResponse, ApiError> {
+    let body = state_shared.metrics.render(&state_shared).await.map_err(ApiError::c500)?;
+
+    Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body))
+}
@@ -1,5 +1,9 @@
+pub(crate) mod admin;
+pub(crate) mod health;
 pub(crate) mod helpers;
 pub(crate) mod jira;
+pub(crate) mod metrics;
 pub(crate) mod ms_oauth;
 pub(crate) mod teams;
+pub(crate) mod teams_action;
 pub(crate) mod teams_lifecycle;
\ No newline at end of file
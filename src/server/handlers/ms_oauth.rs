@@ -18,11 +18,11 @@ pub(crate) async fn handler(
     Form(data): Form<OAuthRequest>,
 ) -> Html<String> {
 
-    if state_shared.microsoft.state.lock().await.subscription.check_client_secret(&data.state).is_err() {
+    if state_shared.microsoft.state.lock().await.check_auth_state(&data.state).is_err() {
         return get_html("Error", "Failed to check secret");
     }
 
-    if state_shared.microsoft.set_delegated_token(data.code).await.is_err() {
+    if state_shared.microsoft.set_delegated_token(&state_shared.store, data.code).await.is_err() {
         return get_html("Error", "Failed to set delegated token");
     }
 
@@ -1,15 +1,19 @@
 use axum::{
-    extract::{Query, State}, 
+    extract::{Extension, Query, State},
     http::StatusCode, 
     response::Result, 
     body::Bytes,
 };
 use serde::Deserialize;
-use tracing::error;
+use serde_json::{json, Value};
+use tracing::{error, info, warn};
 
 use crate::{
-    jira_api::{comment::JiraComment, issue::Issue}, 
-    ms_graph_api::message::MsGraphMessage, server::AppStateShared, 
+    jira_api::{comment::JiraComment, issue::Issue},
+    logging::model::CorrelationId,
+    ms_graph_api::{message::MsGraphMessage, model::MsUser}, 
+    server::{error::Error, AppStateShared}, 
+    store::event::EventSource,
 };
 
 use super::helpers;
@@ -24,13 +28,16 @@ pub(crate) struct Request {
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct RequestValue {
-    // pub(crate) change_type: String,
+    #[serde(default)]
+    pub(crate) change_type: String,
     pub(crate) client_state: String,
     pub(crate) resource: String,
+    pub(crate) subscription_id: String,
 }
 pub(crate) async fn handler(
     Query(query): Query<helpers::ValidationTokenQuery>, 
     State(state_shared): State<AppStateShared>,
+    Extension(correlation_id): Extension<CorrelationId>,
     body: Bytes,
 ) -> Result<(StatusCode, String)> {
     if !body.is_empty() {
@@ -39,16 +46,34 @@ pub(crate) async fn handler(
             (StatusCode::BAD_REQUEST, e.to_string())
         })?;
 
-        tokio::task::spawn(
-            async move {
-                if let Err(e) = handle_teams_request(request, state_shared).await {
-                    error!("Failed to handle teams request: {}", e);
-                    Err(e)
-                } else {
-                    Ok(())
-                }
-            }
-        );
+        // Secret changes with every new subscription, so it's checked before the request is queued
+        let mut tx = state_shared.microsoft.state.lock().await;
+
+        for value in request.value.iter().flatten() {
+            tx.check_client_secret(&value.subscription_id, &value.client_state)
+                .map_err(|e| {
+                    error!("Failed to check secret: {}", e);
+                    (StatusCode::BAD_REQUEST, e.to_string())
+                })?
+                .notified();
+        }
+
+        drop(tx);
+
+        // One event per notification, so a retry doesn't repeat notifications which are already synced
+        let payload = serde_json::from_slice::<Value>(&body).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+        for value in payload["value"].as_array().into_iter().flatten() {
+            let event = json!({ "value": [value] }).to_string();
+
+            state_shared.outbox
+                .push(&state_shared, EventSource::Teams, "notification", event.as_bytes(), &correlation_id)
+                .await
+                .map_err(|e| {
+                    error!("Failed to accept teams request: {:#}", e);
+                    Error::c500(e)
+                })?;
+        }
     }
 
     let response = query.validation_token.clone().unwrap_or_default();
@@ -56,56 +81,80 @@ pub(crate) async fn handler(
     Ok((StatusCode::OK, response))
 }
 
-async fn handle_teams_request(request: Request, state_shared: AppStateShared) -> anyhow::Result<()> {
-    let mut tx = state_shared.microsoft.state.lock().await;
-
-    let token = match tx.token.get() {
-        Ok(t) => t,
-        Err(_) => {
-            tx.token.renew(&state_shared.microsoft.client, &state_shared.microsoft.config).await?
-        },
-    };
+pub(crate) async fn handle_teams_request(request: Request, state_shared: AppStateShared) -> anyhow::Result<()> {
+    // The state isn't held while processing, webhooks check subscription secrets with it
+    let token = state_shared.microsoft.get_token().await?;
 
     if let Some(values) = request.value {
         for value in values {
-            tx.subscription.check_client_secret(&value.client_state)?;
-
             let (maybe_message_id, maybe_reply_id) = helpers::get_message_id_and_reply_id(&value.resource);
             
+            let Some(route) = helpers::get_group_id_and_channel_id(&value.resource)
+                .and_then(|(group_id, channel_id)| state_shared.routes.find_by_channel(&group_id, &channel_id))
+            else {
+                warn!("No route for resource {}", value.resource);
+                continue;
+            };
+
             if let Some(message_id) = maybe_message_id {
                 let message = MsGraphMessage::get(&state_shared.microsoft.client, &value.resource, &token).await?;
 
-                let user_email = match message.from.user {
-                    Some(u) => state_shared.microsoft.get_user_email(&token, u.id).await?,
-                    None => String::new(),
+                let user = match message.from.user {
+                    Some(u) => state_shared.microsoft.get_user(&token, u.id).await?,
+                    None => MsUser::default(),
                 };
 
-                if user_email == state_shared.microsoft.config.teams_user {
+                if user.mail == state_shared.microsoft.config.teams_user {
                     continue;
                 }
 
+                let deleted = value.change_type == "deleted" || message.deleted_date_time.is_some();
+
                 if let Some(reply_id) = maybe_reply_id {
                     let message_url = &value.resource.split("/replies").next().unwrap_or_default().to_string();
                     let parent_message = MsGraphMessage::get(&state_shared.microsoft.client, message_url, &token).await?;
 
+                    if deleted {
+                        JiraComment::delete_by_reply(
+                                state_shared.clone(),
+                                route,
+                                &parent_message.web_url.unwrap_or_default(),
+                                &reply_id,
+                                &message_id,
+                            )
+                            .await?;
+
+                        continue;
+                    }
+
                     JiraComment::create_or_update(
                             state_shared.clone(),
+                            route,
                             &message.body.content, 
-                            &user_email, 
+                            &user, 
                             &message.attachments,
+                            &message.mentions,
                             &token,
                             &parent_message.web_url.unwrap_or_default(),
                             &reply_id,
                             &message_id,
                         )
                         .await?;
-                } else {
+
+                    // User replied while the issue waits for confirmation, support team decides what's next
+                    if value.change_type == "created" && state_shared.store.cancel_closure_by_message(&route.channel_id, &message_id).await? {
+                        info!("Automatic closure of the issue of message {} is cancelled by a reply", message_id);
+                    }
+                } else if !deleted {
+                    // Deleted root messages are left in Jira, the issue may be already in progress
                     let (issue, issue_exists) = Issue::create_or_update(
                             state_shared.clone(),
+                            route,
                             &message.subject.unwrap_or_default(), 
                             &message.body.content, 
-                            &user_email, 
+                            &user, 
                             &message.attachments,
+                            &message.mentions,
                             &token,
                             &message.web_url.unwrap_or_default(),
                             &message_id,
@@ -113,11 +162,9 @@ async fn handle_teams_request(request: Request, state_shared: AppStateShared) ->
                         .await?;
 
                     if !issue_exists {
-                        let url = format!("{}/browse/{}", state_shared.jira.config.base_url, issue.get_key());
+                        let locale = state_shared.store.get_issue_locale(&route.channel_id, &message_id).await?;
 
-                        state_shared.microsoft
-                            .reply_to_issue(&message_id, &format!("<a href=\"{}\">{}</a>", url, url))
-                            .await?;
+                        issue.notify(&state_shared, route, &message_id, locale.as_deref(), None, None).await?;
                     }
                 }
             }
@@ -0,0 +1,132 @@
+use anyhow::{Context, Result};
+use axum::extract::{Form, Query, State};
+use axum::response::{Html, Result as ApiResult};
+use serde::Deserialize;
+use tracing::info;
+
+use crate::jira_api::issue::Issue;
+use crate::jira_api::transition::JiraTransition;
+use crate::ms_graph_api::action::{IssueAction, SignedAction};
+use crate::ms_graph_api::model::MsUser;
+use crate::server::error::Error as ApiError;
+use crate::server::AppStateShared;
+use crate::utils::{fill_html_template, html_escape};
+
+use super::helpers::extract_message_id_from_url;
+
+/// Sign-in response posted by Microsoft.
+#[derive(Deserialize)]
+pub(crate) struct SignInResponse {
+    code: String,
+    state: String,
+}
+
+/// Confirmation page of the card button. Buttons are `Action.OpenUrl` links, which link scanners may open
+/// too, so the issue is moved only after the user signs in and Microsoft posts the result to `handler`.
+pub(crate) async fn confirm(
+    State(state_shared): State<AppStateShared>,
+    Query(signed): Query<SignedAction>,
+) -> ApiResult<Html<String>, ApiError> {
+    let action = signed.verify(&state_shared.microsoft.config.action_secret).map_err(|_| ApiError::c401())?;
+    let page = ActionPage::new(&state_shared, &signed.issue, action).await.map_err(ApiError::c500)?;
+
+    if signed.is_expired() {
+        return Ok(page.render("action_expired", ""));
+    }
+
+    let sign_in_url = state_shared.microsoft.action_sign_in_url(&signed.to_state()).map_err(ApiError::c500)?;
+    let button = format!(
+        "<p><a href=\"{}\">{}</a></p>",
+        html_escape(&sign_in_url),
+        state_shared.i18n.get(page.locale.as_deref(), "action_sign_in"),
+    );
+
+    Ok(page.render("action_confirm", &button))
+}
+
+/// Moves the issue after the user signed in on the confirmation page.
+pub(crate) async fn handler(
+    State(state_shared): State<AppStateShared>,
+    Form(response): Form<SignInResponse>,
+) -> ApiResult<Html<String>, ApiError> {
+    let signed = SignedAction::from_state(&response.state).map_err(ApiError::c400)?;
+    let action = signed.verify(&state_shared.microsoft.config.action_secret).map_err(|_| ApiError::c401())?;
+    let page = ActionPage::new(&state_shared, &signed.issue, action).await.map_err(ApiError::c500)?;
+
+    if signed.is_expired() {
+        return Ok(page.render("action_expired", ""));
+    }
+
+    let user = state_shared.microsoft
+        .get_signed_in_user(&response.code)
+        .await
+        .map_err(|_| ApiError::c401())?;
+
+    let template = page.apply(&user).await.map_err(ApiError::c500)?;
+
+    Ok(page.render(template, ""))
+}
+
+/// Result page of the card button.
+struct ActionPage<'a> {
+    state_shared: &'a AppStateShared,
+    issue: Issue,
+    action: IssueAction,
+    locale: Option<String>,
+}
+
+impl<'a> ActionPage<'a> {
+    async fn new(state_shared: &'a AppStateShared, issue_id: &str, action: IssueAction) -> Result<Self> {
+        let issue = Issue::get_issue(&state_shared.jira, issue_id).await.context("Failed to get issue")?;
+
+        let locale = match state_shared.routes.find_by_issue(&issue).and_then(|(route, link)| Some((route, extract_message_id_from_url(link)?))) {
+            Some((route, message_id)) => state_shared.store.get_issue_locale(&route.channel_id, &message_id).await?,
+            None => None,
+        };
+
+        Ok(Self { state_shared, issue, action, locale })
+    }
+
+    fn target(&self) -> &str {
+        let config = &self.state_shared.jira.config;
+
+        match self.action {
+            IssueAction::Confirm => &config.confirmed_status,
+            IssueAction::Reject => &config.rejected_status,
+        }
+    }
+
+    /// Moves the issue, returns the catalog key of the result.
+    async fn apply(&self, user: &MsUser) -> Result<&'static str> {
+        let state_shared = self.state_shared;
+        let current = self.issue.get_status().unwrap_or_default();
+
+        // Buttons of the card stay clickable after the status has changed
+        let moved = state_shared.jira.config.confirm_statuses.contains(&current)
+            && JiraTransition::to_status(&state_shared.jira, &self.issue.get_id(), self.target())
+                .await
+                .context("Failed to transition issue")?;
+
+        info!(
+            user = %user.mail,
+            action = self.action.as_str(),
+            moved,
+            "Card action on issue {} by {}", self.issue.get_key(), user.mail,
+        );
+
+        Ok(if moved { "action_done" } else { "action_unavailable" })
+    }
+
+    fn render(&self, template: &str, extra: &str) -> Html<String> {
+        let key = self.issue.get_key();
+        let url = format!("{}/browse/{}", self.state_shared.jira.config.base_url, key);
+        let current = self.issue.get_status().unwrap_or_default();
+
+        let text = fill_html_template(
+            self.state_shared.i18n.get(self.locale.as_deref(), template),
+            &[("key", &key), ("url", &url), ("status", self.target()), ("current", &current)],
+        );
+
+        Html(format!("<p>{}</p>{}", text, extra))
+    }
+}
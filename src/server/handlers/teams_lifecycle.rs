@@ -61,29 +61,32 @@ pub(crate) async fn handler(
 }
 
 async fn parse_handler(graph_api: &MSGraphAPI, request: Request) -> anyhow::Result<()> {
-    let mut tx = graph_api.state.lock().await;
-        
-    let token = tx.token.renew(&graph_api.client, &graph_api.config).await.context("Failed to get token")?;
+    let token = graph_api.get_token().await.context("Failed to get token")?;
 
     if let Some(values) = request.value {
         for value in values {
-            tx.subscription.check_client_secret(&value.client_state).context("Failed to check secret")?;
+            let (index, mut subscription) = graph_api
+                .checked_subscription(&value.subscription_id, &value.client_state)
+                .await
+                .context("Failed to check secret")?;
 
             match value.lifecycle_event.as_str() {
                 "reauthorizationRequired" => {
-                        tx.subscription
-                            .renew(&graph_api.client, &token, &value.subscription_id)
+                        subscription
+                            .renew(&graph_api.client, &token)
                             .await
                             .context("Failed to renew subscription")?;
                     },
                 "subscriptionRemoved" => {
-                        tx.subscription
+                        subscription
                             .init(&graph_api.client, &graph_api.config, &token, false)
                             .await
                             .context("Failed to init new subscription")?;
                     },
-                _ => (),
+                _ => continue,
             }
+
+            graph_api.update_subscription(index, subscription).await;
         }
     }
 
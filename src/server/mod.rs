@@ -3,13 +3,23 @@ pub(crate) mod error;
 pub(crate) mod handlers;
 
 use crate::cfg::Config;
+use crate::i18n::model::Catalog;
 use crate::jira_api::model::JiraAPI;
-use crate::server::handlers::{jira, teams, teams_lifecycle, ms_oauth};
+use crate::logging::model::correlate;
+use crate::metrics::model::Metrics;
+use crate::server::handlers::{admin, health, jira, metrics, teams, teams_action, teams_lifecycle, ms_oauth};
 use crate::ms_graph_api::model::MSGraphAPI;
+use crate::notifier::model::Notifier;
+use crate::outbox::model::Outbox;
+use crate::reconciler::model::Reconciler;
+use crate::routes::model::Routes;
+use crate::scheduler::model::Scheduler;
+use crate::store::model::Store;
 use anyhow::{ Context, Result };
 use axum::{
     Router,
-    routing::post,
+    middleware,
+    routing::{delete, get, post},
 };
 use axum_server::Handle;
 use std::net::SocketAddr;
@@ -26,6 +36,14 @@ pub struct Server {
 pub struct AppState {
     pub jira: JiraAPI,
     pub microsoft: MSGraphAPI,
+    pub routes: Routes,
+    pub store: Store,
+    pub outbox: Outbox,
+    pub scheduler: Scheduler,
+    pub reconciler: Reconciler,
+    pub i18n: Catalog,
+    pub metrics: Metrics,
+    pub notifier: Notifier,
 }
 
 pub type AppStateShared = Arc<AppState>;
@@ -41,14 +59,44 @@ impl Server {
         // Middleware ordering matters!
         // Request processing starts from last layer.
         // Response processing starts from first layer.
-        let router = Router::new()
+        let mut router = Router::new()
             // API router.
             .route("/jira", post(jira::handler))
             .route("/teams", post(teams::handler))
             .route("/teams_lifecycle", post(teams_lifecycle::handler))
+            .route("/teams_action", get(teams_action::confirm).post(teams_action::handler))
             .route("/ms_oauth", post(ms_oauth::handler))
+            // Health checks.
+            .route("/healthz", get(health::healthz))
+            .route("/readyz", get(health::readyz));
+
+        // Metrics are public unless a token is configured.
+        let mut metrics_router = Router::new().route("/metrics", get(metrics::handler));
+
+        if !cfg.server.metrics_token.is_empty() {
+            metrics_router = metrics_router.layer(middleware::from_fn_with_state(cfg.server.metrics_token.clone(), admin::auth));
+        }
+
+        router = router.merge(metrics_router);
+
+        // Admin API is available only when a token is configured.
+        if !cfg.server.admin_token.is_empty() {
+            let admin_router = Router::new()
+                .route("/events", get(admin::list_events))
+                .route("/events/{id}", delete(admin::discard_event))
+                .route("/events/{id}/replay", post(admin::replay_event))
+                .route("/reconcile", post(admin::reconcile))
+                .route("/subscriptions", get(admin::list_subscriptions))
+                .layer(middleware::from_fn_with_state(cfg.server.admin_token.clone(), admin::auth));
+
+            router = router.nest("/admin", admin_router);
+        }
+
+        let router = router
             // Injects MS Graph API.
             .with_state(state_shared)
+            // Correlation ID and request logging.
+            .layer(middleware::from_fn(correlate))
             // Compression.
             .layer(CompressionLayer::new());
         
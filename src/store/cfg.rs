@@ -0,0 +1,7 @@
+use envconfig::Envconfig;
+
+#[derive(Envconfig, Clone)]
+pub struct Config {
+    #[envconfig(from = "STORE_PATH", default = "sync_msteams_jira_comments.db")]
+    pub(crate) path: String,
+}
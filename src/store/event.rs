@@ -0,0 +1,275 @@
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension, Params};
+
+use super::model::Store;
+
+/// Webhook accepted by the server and waiting to be processed.
+#[derive(Clone, Debug)]
+pub(crate) struct Event {
+    pub(crate) id: i64,
+    pub(crate) source: EventSource,
+    pub(crate) kind: String,
+    pub(crate) payload: Vec<u8>,
+    pub(crate) status: EventStatus,
+    pub(crate) attempts: u32,
+    pub(crate) last_error: Option<String>,
+    pub(crate) created_at: String,
+    pub(crate) updated_at: String,
+    /// ID of the webhook request, `None` for events queued before it was recorded.
+    pub(crate) correlation_id: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum EventSource {
+    Jira,
+    Teams,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum EventStatus {
+    Pending,
+    Processing,
+    Dead,
+}
+
+impl EventSource {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Self::Jira => "jira",
+            Self::Teams => "teams",
+        }
+    }
+
+    fn parse(value: &str) -> Result<Self> {
+        match value {
+            "jira" => Ok(Self::Jira),
+            "teams" => Ok(Self::Teams),
+            _ => bail!("Unknown event source: {value}"),
+        }
+    }
+}
+
+impl EventStatus {
+    pub(crate) const ALL: [Self; 3] = [Self::Pending, Self::Processing, Self::Dead];
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Processing => "processing",
+            Self::Dead => "dead",
+        }
+    }
+
+    pub(crate) fn parse(value: &str) -> Result<Self> {
+        match value {
+            "pending" => Ok(Self::Pending),
+            "processing" => Ok(Self::Processing),
+            "dead" => Ok(Self::Dead),
+            _ => bail!("Unknown event status: {value}"),
+        }
+    }
+}
+
+const EVENT_COLUMNS: &str = "id, source, kind, payload, status, attempts, last_error, created_at, updated_at, correlation_id";
+
+impl Store {
+    pub(crate) async fn enqueue_event(&self, source: EventSource, kind: &str, payload: &[u8], correlation_id: &str) -> Result<i64> {
+        let connection = self.connection.lock().await;
+
+        connection
+            .execute(
+                "INSERT INTO events (source, kind, payload, status, next_attempt_at, correlation_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![source.as_str(), kind, payload, EventStatus::Pending.as_str(), Utc::now().timestamp(), correlation_id],
+            )
+            .context("Failed to save event")?;
+
+        Ok(connection.last_insert_rowid())
+    }
+
+    /// Takes the oldest pending event which is due and marks it as being processed.
+    pub(crate) async fn claim_next_event(&self) -> Result<Option<Event>> {
+        claim(
+            &mut *self.connection.lock().await,
+            "status = ?1 AND next_attempt_at <= ?2 ORDER BY id LIMIT 1",
+            params![EventStatus::Pending.as_str(), Utc::now().timestamp()],
+        )
+    }
+
+    pub(crate) async fn complete_event(&self, id: i64) -> Result<()> {
+        self.connection
+            .lock()
+            .await
+            .execute("DELETE FROM events WHERE id = ?1", params![id])
+            .context("Failed to complete event")?;
+
+        Ok(())
+    }
+
+    /// Records a failed attempt. The event is retried at `retry_at` (unix time),
+    /// or moved to the dead-letter state when `retry_at` is `None`.
+    pub(crate) async fn fail_event(&self, id: i64, error: &str, retry_at: Option<i64>) -> Result<()> {
+        let status = match retry_at {
+            Some(_) => EventStatus::Pending,
+            None => EventStatus::Dead,
+        };
+
+        self.connection
+            .lock()
+            .await
+            .execute(
+                "UPDATE events SET
+                    status = ?2,
+                    attempts = attempts + 1,
+                    next_attempt_at = COALESCE(?3, next_attempt_at),
+                    last_error = ?4,
+                    updated_at = CURRENT_TIMESTAMP
+                WHERE id = ?1",
+                params![id, status.as_str(), retry_at, error],
+            )
+            .context("Failed to save event failure")?;
+
+        Ok(())
+    }
+
+    /// Lists the latest events which failed at least once, optionally filtered by status.
+    pub(crate) async fn list_failed_events(&self, status: Option<EventStatus>, limit: u32) -> Result<Vec<Event>> {
+        let connection = self.connection.lock().await;
+
+        let mut statement = connection.prepare(&format!(
+            "SELECT {EVENT_COLUMNS} FROM events
+                WHERE last_error IS NOT NULL AND (?1 IS NULL OR status = ?1)
+                ORDER BY id DESC LIMIT ?2"
+        ))?;
+
+        let rows = statement
+            .query_map(params![status.map(|s| s.as_str()), limit], EventRow::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("Failed to select events")?;
+
+        rows.into_iter().map(EventRow::into_event).collect()
+    }
+
+    /// Number of queued events of every status.
+    pub(crate) async fn count_events(&self) -> Result<Vec<(EventStatus, i64)>> {
+        let connection = self.connection.lock().await;
+        let mut statement = connection.prepare("SELECT COUNT(*) FROM events WHERE status = ?1")?;
+
+        EventStatus::ALL
+            .into_iter()
+            .map(|status| {
+                statement
+                    .query_row(params![status.as_str()], |row| row.get(0))
+                    .map(|count| (status, count))
+                    .context("Failed to count events")
+            })
+            .collect()
+    }
+
+    /// Marks the event as being processed unless a worker already took it.
+    pub(crate) async fn claim_event(&self, id: i64) -> Result<Option<Event>> {
+        claim(
+            &mut *self.connection.lock().await,
+            "id = ?1 AND status <> ?2",
+            params![id, EventStatus::Processing.as_str()],
+        )
+    }
+
+    /// Deletes the event unless it's being processed. Returns `false` if there was nothing to delete.
+    pub(crate) async fn discard_event(&self, id: i64) -> Result<bool> {
+        let deleted = self.connection
+            .lock()
+            .await
+            .execute(
+                "DELETE FROM events WHERE id = ?1 AND status <> ?2",
+                params![id, EventStatus::Processing.as_str()],
+            )
+            .context("Failed to discard event")?;
+
+        Ok(deleted > 0)
+    }
+
+    /// Returns events interrupted by a shutdown back to the queue.
+    pub(crate) async fn requeue_processing_events(&self) -> Result<usize> {
+        self.connection
+            .lock()
+            .await
+            .execute(
+                "UPDATE events SET status = ?1, updated_at = CURRENT_TIMESTAMP WHERE status = ?2",
+                params![EventStatus::Pending.as_str(), EventStatus::Processing.as_str()],
+            )
+            .context("Failed to requeue events")
+    }
+}
+
+fn claim(connection: &mut Connection, condition: &str, params: impl Params) -> Result<Option<Event>> {
+    let tx = connection.transaction()?;
+
+    let row = tx
+        .query_row(&format!("SELECT {EVENT_COLUMNS} FROM events WHERE {condition}"), params, EventRow::from_row)
+        .optional()
+        .context("Failed to select event")?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    tx.execute(
+        "UPDATE events SET status = ?2, updated_at = CURRENT_TIMESTAMP WHERE id = ?1",
+        params![row.id, EventStatus::Processing.as_str()],
+    )
+    .context("Failed to claim event")?;
+
+    tx.commit()?;
+
+    let mut event = row.into_event()?;
+    event.status = EventStatus::Processing;
+
+    Ok(Some(event))
+}
+
+/// Raw `events` row, converted to [`Event`] outside of rusqlite callbacks.
+struct EventRow {
+    id: i64,
+    source: String,
+    kind: String,
+    payload: Vec<u8>,
+    status: String,
+    attempts: u32,
+    last_error: Option<String>,
+    created_at: String,
+    updated_at: String,
+    correlation_id: Option<String>,
+}
+
+impl EventRow {
+    fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            source: row.get(1)?,
+            kind: row.get(2)?,
+            payload: row.get(3)?,
+            status: row.get(4)?,
+            attempts: row.get(5)?,
+            last_error: row.get(6)?,
+            created_at: row.get(7)?,
+            updated_at: row.get(8)?,
+            correlation_id: row.get(9)?,
+        })
+    }
+
+    fn into_event(self) -> Result<Event> {
+        Ok(Event {
+            id: self.id,
+            source: EventSource::parse(&self.source)?,
+            kind: self.kind,
+            payload: self.payload,
+            status: EventStatus::parse(&self.status)?,
+            attempts: self.attempts,
+            last_error: self.last_error,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            correlation_id: self.correlation_id,
+        })
+    }
+}
@@ -0,0 +1,4 @@
+pub(crate) mod cfg;
+pub(crate) mod event;
+pub mod model;
+pub(crate) mod schedule;
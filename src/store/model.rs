@@ -0,0 +1,373 @@
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use tokio::sync::Mutex;
+
+use super::cfg::Config;
+
+/// Schema migrations, applied in order. The index of the last applied migration
+/// is kept in SQLite `user_version`, so new migrations must only be appended.
+///
+/// Teams message and reply IDs are unique only within the channel, so they are keyed along with
+/// the channel ID, which is unique across teams.
+const MIGRATIONS: &[&str] = &[
+    r#"
+    CREATE TABLE issues (
+        channel_id TEXT NOT NULL,
+        message_id TEXT NOT NULL,
+        issue_id TEXT NOT NULL,
+        updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        PRIMARY KEY (channel_id, message_id)
+    );
+    CREATE INDEX issues_issue_id ON issues (issue_id);
+
+    CREATE TABLE comments (
+        channel_id TEXT NOT NULL,
+        reply_id TEXT NOT NULL,
+        message_id TEXT NOT NULL,
+        issue_id TEXT NOT NULL,
+        comment_id TEXT NOT NULL,
+        updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        PRIMARY KEY (channel_id, reply_id)
+    );
+    CREATE INDEX comments_comment_id ON comments (comment_id);
+    "#,
+    r#"
+    CREATE TABLE events (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        source TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        payload BLOB NOT NULL,
+        status TEXT NOT NULL,
+        attempts INTEGER NOT NULL DEFAULT 0,
+        next_attempt_at INTEGER NOT NULL,
+        last_error TEXT,
+        created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );
+    CREATE INDEX events_status_next_attempt_at ON events (status, next_attempt_at);
+    "#,
+    r#"
+    ALTER TABLE issues ADD COLUMN locale TEXT;
+    "#,
+    r#"
+    CREATE TABLE bot_replies (
+        channel_id TEXT NOT NULL,
+        message_id TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        reply_id TEXT NOT NULL,
+        updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        PRIMARY KEY (channel_id, message_id, kind)
+    );
+    "#,
+    r#"
+    CREATE TABLE files (
+        drive_item_id TEXT NOT NULL,
+        issue_id TEXT NOT NULL,
+        attachment_id TEXT NOT NULL,
+        updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        PRIMARY KEY (drive_item_id, issue_id)
+    );
+    "#,
+    r#"
+    CREATE TABLE closures (
+        issue_id TEXT PRIMARY KEY,
+        channel_id TEXT NOT NULL,
+        message_id TEXT NOT NULL,
+        remind_at INTEGER,
+        close_at INTEGER NOT NULL,
+        updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );
+    CREATE INDEX closures_message_id ON closures (channel_id, message_id);
+    "#,
+    r#"
+    CREATE TABLE secrets (
+        name TEXT PRIMARY KEY,
+        value BLOB NOT NULL,
+        updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );
+    "#,
+    r#"
+    ALTER TABLE events ADD COLUMN correlation_id TEXT;
+    "#,
+];
+
+/// Embedded storage of links between Teams messages and Jira entities.
+pub struct Store {
+    pub(super) connection: Mutex<Connection>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct CommentLink {
+    pub(crate) channel_id: String,
+    pub(crate) reply_id: String,
+    pub(crate) message_id: String,
+    pub(crate) issue_id: String,
+    pub(crate) comment_id: String,
+}
+
+impl Store {
+    pub fn new(config: Config) -> Result<Self> {
+        let mut connection = Connection::open(&config.path)
+            .with_context(|| format!("Failed to open store at {}", config.path))?;
+
+        connection
+            .pragma_update(None, "journal_mode", "WAL")
+            .context("Failed to enable WAL journal")?;
+
+        migrate(&mut connection).context("Failed to migrate store")?;
+
+        Ok(Self { connection: Mutex::new(connection) })
+    }
+
+    pub(crate) async fn get_issue_id(&self, channel_id: &str, message_id: &str) -> Result<Option<String>> {
+        self.connection
+            .lock()
+            .await
+            .query_row(
+                "SELECT issue_id FROM issues WHERE channel_id = ?1 AND message_id = ?2",
+                params![channel_id, message_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("Failed to select issue link")
+    }
+
+    pub(crate) async fn set_issue_id(&self, channel_id: &str, message_id: &str, issue_id: &str) -> Result<()> {
+        self.connection
+            .lock()
+            .await
+            .execute(
+                "INSERT INTO issues (channel_id, message_id, issue_id) VALUES (?1, ?2, ?3)
+                    ON CONFLICT (channel_id, message_id) DO UPDATE SET issue_id = excluded.issue_id, updated_at = CURRENT_TIMESTAMP",
+                params![channel_id, message_id, issue_id],
+            )
+            .context("Failed to save issue link")?;
+
+        Ok(())
+    }
+
+    /// Locale of the Teams user who started the thread.
+    pub(crate) async fn get_issue_locale(&self, channel_id: &str, message_id: &str) -> Result<Option<String>> {
+        self.connection
+            .lock()
+            .await
+            .query_row(
+                "SELECT locale FROM issues WHERE channel_id = ?1 AND message_id = ?2",
+                params![channel_id, message_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map(Option::flatten)
+            .context("Failed to select issue locale")
+    }
+
+    pub(crate) async fn set_issue_locale(&self, channel_id: &str, message_id: &str, locale: &str) -> Result<()> {
+        self.connection
+            .lock()
+            .await
+            .execute(
+                "UPDATE issues SET locale = ?3, updated_at = CURRENT_TIMESTAMP WHERE channel_id = ?1 AND message_id = ?2",
+                params![channel_id, message_id, locale],
+            )
+            .context("Failed to save issue locale")?;
+
+        Ok(())
+    }
+
+    pub(crate) async fn remove_issue_id(&self, channel_id: &str, message_id: &str) -> Result<()> {
+        self.connection
+            .lock()
+            .await
+            .execute("DELETE FROM issues WHERE channel_id = ?1 AND message_id = ?2", params![channel_id, message_id])
+            .context("Failed to delete issue link")?;
+
+        Ok(())
+    }
+
+    /// Bot reply of the given kind, which is edited in place instead of posting a new one.
+    pub(crate) async fn get_bot_reply(&self, channel_id: &str, message_id: &str, kind: &str) -> Result<Option<String>> {
+        self.connection
+            .lock()
+            .await
+            .query_row(
+                "SELECT reply_id FROM bot_replies WHERE channel_id = ?1 AND message_id = ?2 AND kind = ?3",
+                params![channel_id, message_id, kind],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("Failed to select bot reply")
+    }
+
+    pub(crate) async fn set_bot_reply(&self, channel_id: &str, message_id: &str, kind: &str, reply_id: &str) -> Result<()> {
+        self.connection
+            .lock()
+            .await
+            .execute(
+                "INSERT INTO bot_replies (channel_id, message_id, kind, reply_id) VALUES (?1, ?2, ?3, ?4)
+                    ON CONFLICT (channel_id, message_id, kind) DO UPDATE SET reply_id = excluded.reply_id, updated_at = CURRENT_TIMESTAMP",
+                params![channel_id, message_id, kind, reply_id],
+            )
+            .context("Failed to save bot reply")?;
+
+        Ok(())
+    }
+
+    /// Jira attachment with the copy of Teams file (drive item) in the issue.
+    pub(crate) async fn get_file(&self, drive_item_id: &str, issue_id: &str) -> Result<Option<String>> {
+        self.connection
+            .lock()
+            .await
+            .query_row(
+                "SELECT attachment_id FROM files WHERE drive_item_id = ?1 AND issue_id = ?2",
+                params![drive_item_id, issue_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("Failed to select file")
+    }
+
+    pub(crate) async fn set_file(&self, drive_item_id: &str, issue_id: &str, attachment_id: &str) -> Result<()> {
+        self.connection
+            .lock()
+            .await
+            .execute(
+                "INSERT INTO files (drive_item_id, issue_id, attachment_id) VALUES (?1, ?2, ?3)
+                    ON CONFLICT (drive_item_id, issue_id) DO UPDATE SET attachment_id = excluded.attachment_id, updated_at = CURRENT_TIMESTAMP",
+                params![drive_item_id, issue_id, attachment_id],
+            )
+            .context("Failed to save file")?;
+
+        Ok(())
+    }
+
+    /// Encrypted secret, like the refresh token of the delegated access.
+    pub(crate) async fn get_secret(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        self.connection
+            .lock()
+            .await
+            .query_row(
+                "SELECT value FROM secrets WHERE name = ?1",
+                params![name],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("Failed to select secret")
+    }
+
+    pub(crate) async fn set_secret(&self, name: &str, value: &[u8]) -> Result<()> {
+        self.connection
+            .lock()
+            .await
+            .execute(
+                "INSERT INTO secrets (name, value) VALUES (?1, ?2)
+                    ON CONFLICT (name) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+                params![name, value],
+            )
+            .context("Failed to save secret")?;
+
+        Ok(())
+    }
+
+    pub(crate) async fn remove_secret(&self, name: &str) -> Result<()> {
+        self.connection
+            .lock()
+            .await
+            .execute("DELETE FROM secrets WHERE name = ?1", params![name])
+            .context("Failed to delete secret")?;
+
+        Ok(())
+    }
+
+    pub(crate) async fn get_comment_by_reply_id(&self, channel_id: &str, reply_id: &str) -> Result<Option<CommentLink>> {
+        self.connection
+            .lock()
+            .await
+            .query_row(
+                "SELECT channel_id, reply_id, message_id, issue_id, comment_id FROM comments WHERE channel_id = ?1 AND reply_id = ?2",
+                params![channel_id, reply_id],
+                comment_link_from_row,
+            )
+            .optional()
+            .context("Failed to select comment link")
+    }
+
+    /// Comment IDs are unique across Jira, so the channel isn't needed.
+    pub(crate) async fn get_comment_by_comment_id(&self, comment_id: &str) -> Result<Option<CommentLink>> {
+        self.connection
+            .lock()
+            .await
+            .query_row(
+                "SELECT channel_id, reply_id, message_id, issue_id, comment_id FROM comments WHERE comment_id = ?1",
+                params![comment_id],
+                comment_link_from_row,
+            )
+            .optional()
+            .context("Failed to select comment link")
+    }
+
+    /// Unix time of the last sync of the reply with the comment.
+    pub(crate) async fn get_comment_synced_at(&self, channel_id: &str, reply_id: &str) -> Result<Option<i64>> {
+        self.connection
+            .lock()
+            .await
+            .query_row(
+                "SELECT CAST(strftime('%s', updated_at) AS INTEGER) FROM comments WHERE channel_id = ?1 AND reply_id = ?2",
+                params![channel_id, reply_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("Failed to select comment sync time")
+    }
+
+    pub(crate) async fn set_comment(&self, link: &CommentLink) -> Result<()> {
+        self.connection
+            .lock()
+            .await
+            .execute(
+                "INSERT INTO comments (channel_id, reply_id, message_id, issue_id, comment_id) VALUES (?1, ?2, ?3, ?4, ?5)
+                    ON CONFLICT (channel_id, reply_id) DO UPDATE SET
+                        message_id = excluded.message_id,
+                        issue_id = excluded.issue_id,
+                        comment_id = excluded.comment_id,
+                        updated_at = CURRENT_TIMESTAMP",
+                params![link.channel_id, link.reply_id, link.message_id, link.issue_id, link.comment_id],
+            )
+            .context("Failed to save comment link")?;
+
+        Ok(())
+    }
+
+    pub(crate) async fn remove_comment(&self, channel_id: &str, reply_id: &str) -> Result<()> {
+        self.connection
+            .lock()
+            .await
+            .execute("DELETE FROM comments WHERE channel_id = ?1 AND reply_id = ?2", params![channel_id, reply_id])
+            .context("Failed to delete comment link")?;
+
+        Ok(())
+    }
+}
+
+fn comment_link_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<CommentLink> {
+    Ok(CommentLink {
+        channel_id: row.get(0)?,
+        reply_id: row.get(1)?,
+        message_id: row.get(2)?,
+        issue_id: row.get(3)?,
+        comment_id: row.get(4)?,
+    })
+}
+
+fn migrate(connection: &mut Connection) -> Result<()> {
+    let version: i64 = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+    for (index, migration) in (0_i64..).zip(MIGRATIONS.iter()).skip(version as usize) {
+        let tx = connection.transaction()?;
+        tx.execute_batch(migration)
+            .with_context(|| format!("Failed to apply migration {}", index + 1))?;
+        tx.pragma_update(None, "user_version", index + 1)?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use anyhow::{Context, Result};
+use rusqlite::params;
+
+use super::model::Store;
+
+/// Issue waiting for confirmation, closed automatically at `close_at` (unix time).
+#[derive(Clone, Debug)]
+pub(crate) struct Closure {
+    pub(crate) issue_id: String,
+    pub(crate) message_id: String,
+    pub(crate) close_at: i64,
+}
+
+impl Store {
+    /// Schedules closure of the issue, replacing the existing schedule. The reminder is skipped if `remind_at` is `None`.
+    pub(crate) async fn schedule_closure(&self, issue_id: &str, channel_id: &str, message_id: &str, remind_at: Option<i64>, close_at: i64) -> Result<()> {
+        self.connection
+            .lock()
+            .await
+            .execute(
+                "INSERT INTO closures (issue_id, channel_id, message_id, remind_at, close_at) VALUES (?1, ?2, ?3, ?4, ?5)
+                    ON CONFLICT (issue_id) DO UPDATE SET
+                        channel_id = excluded.channel_id,
+                        message_id = excluded.message_id,
+                        remind_at = excluded.remind_at,
+                        close_at = excluded.close_at,
+                        updated_at = CURRENT_TIMESTAMP",
+                params![issue_id, channel_id, message_id, remind_at, close_at],
+            )
+            .context("Failed to save closure")?;
+
+        Ok(())
+    }
+
+    pub(crate) async fn cancel_closure(&self, issue_id: &str) -> Result<()> {
+        self.connection
+            .lock()
+            .await
+            .execute("DELETE FROM closures WHERE issue_id = ?1", params![issue_id])
+            .context("Failed to delete closure")?;
+
+        Ok(())
+    }
+
+    /// Cancels closure of the issue started by the message. Returns `false` if there was nothing to cancel.
+    pub(crate) async fn cancel_closure_by_message(&self, channel_id: &str, message_id: &str) -> Result<bool> {
+        let deleted = self.connection
+            .lock()
+            .await
+            .execute("DELETE FROM closures WHERE channel_id = ?1 AND message_id = ?2", params![channel_id, message_id])
+            .context("Failed to delete closure")?;
+
+        Ok(deleted > 0)
+    }
+
+    /// Closures whose reminder is due.
+    pub(crate) async fn list_due_reminders(&self, now: i64) -> Result<Vec<Closure>> {
+        self.list_closures("remind_at <= ?1 AND close_at > ?1", now).await
+    }
+
+    pub(crate) async fn list_due_closures(&self, now: i64) -> Result<Vec<Closure>> {
+        self.list_closures("close_at <= ?1", now).await
+    }
+
+    /// Marks the reminder as posted, so it's posted once.
+    pub(crate) async fn complete_reminder(&self, issue_id: &str) -> Result<()> {
+        self.connection
+            .lock()
+            .await
+            .execute(
+                "UPDATE closures SET remind_at = NULL, updated_at = CURRENT_TIMESTAMP WHERE issue_id = ?1",
+                params![issue_id],
+            )
+            .context("Failed to save reminder")?;
+
+        Ok(())
+    }
+
+    async fn list_closures(&self, condition: &str, now: i64) -> Result<Vec<Closure>> {
+        let connection = self.connection.lock().await;
+
+        let mut statement = connection.prepare(&format!(
+            "SELECT issue_id, message_id, close_at FROM closures WHERE {condition} ORDER BY close_at"
+        ))?;
+
+        statement
+            .query_map(params![now], |row| Ok(Closure {
+                issue_id: row.get(0)?,
+                message_id: row.get(1)?,
+                close_at: row.get(2)?,
+            }))?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("Failed to select closures")
+    }
+}
@@ -2,6 +2,7 @@ use std::time::Duration;
 
 use anyhow::Result;
 use futures::Future;
+use regex::{Captures, Regex};
 use reqwest::Client;
 use tokio::signal::unix::signal;
 use tokio::signal::unix::SignalKind;
@@ -26,4 +27,51 @@ pub(crate) fn get_reqwest_client() -> Result<Client> {
             .use_rustls_tls()
             .build()?
         )
-}
\ No newline at end of file
+}
+
+/// Replaces `{name}` placeholders of the template with values. Placeholders are replaced in one pass,
+/// so ones inside values are left as is.
+pub(crate) fn fill_template(template: &str, values: &[(&str, &str)]) -> String {
+    Regex::new(r"\{(\w+)\}")
+        .unwrap()
+        .replace_all(template, |caps: &Captures| match values.iter().find(|(name, _)| *name == &caps[1]) {
+            Some((_, value)) => value.to_string(),
+            None => caps[0].to_string(),
+        })
+        .into_owned()
+}
+
+/// Fills HTML template with escaped values.
+pub(crate) fn fill_html_template(template: &str, values: &[(&str, &str)]) -> String {
+    let escaped: Vec<(&str, String)> = values.iter().map(|(name, value)| (*name, html_escape(value))).collect();
+    let escaped: Vec<(&str, &str)> = escaped.iter().map(|(name, value)| (*name, value.as_str())).collect();
+
+    fill_template(template, &escaped)
+}
+
+pub(crate) fn html_escape(text: &str) -> String {
+    text
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_in_values_are_kept() {
+        let text = fill_template("{key}: {summary} {unknown}", &[("summary", "see {key}"), ("key", "SUP-1")]);
+
+        assert_eq!(text, "SUP-1: see {key} {unknown}");
+    }
+
+    #[test]
+    fn html_values_are_escaped() {
+        let text = fill_html_template("<a href=\"{url}\">{summary}</a>", &[("url", "https://x/?a=1&b=2"), ("summary", "<b>\"VPN\"</b>")]);
+
+        assert_eq!(text, "<a href=\"https://x/?a=1&amp;b=2\">&lt;b&gt;&quot;VPN&quot;&lt;/b&gt;</a>");
+    }
+}